@@ -0,0 +1,343 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use flate2::{Decompress, FlushDecompress, Status};
+use radiance::compression::Compressor;
+use radiance::encoding::Encoding;
+use serde_etf::{KeyStyle, Value};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: etf-tool <FROM> <TO> [FILE]
+
+Transcodes gateway payloads. Reads FILE, or stdin if it's missing or -, and
+writes to stdout.
+
+Formats:
+    etf         A single ETF term
+    json        JSON values, pretty printed when writing
+    zlib-etf    A zlib-stream session of ETF payloads
+    zlib-json   A zlib-stream session of JSON payloads";
+
+// zlib-stream messages end with a sync flush, which always ends like this
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+// How far back deflate can refer
+const WINDOW_SIZE: usize = 32 * 1024;
+
+#[derive(Copy, Clone)]
+struct Format {
+    encoding: Encoding,
+    zlib: bool,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (encoding, zlib) = match s.strip_prefix("zlib-") {
+            Some(encoding) => (encoding, true),
+            None => (s, false),
+        };
+        Ok(Format {
+            encoding: Encoding::from_str(encoding)?,
+            zlib,
+        })
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let (from, to, file) = match &args[..] {
+        [from, to] => (from, to, None),
+        [from, to, file] => (from, to, Some(file).filter(|f| *f != "-")),
+        _ => exit(USAGE),
+    };
+    let (Ok(from), Ok(to)) = (Format::from_str(from), Format::from_str(to)) else {
+        exit(USAGE)
+    };
+
+    let input = match file {
+        Some(file) => std::fs::read(file).map_err(|e| format!("Can't read {file}: {e}")),
+        None => {
+            let mut input = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut input)
+                .map(|_| input)
+                .map_err(|e| format!("Can't read stdin: {e}"))
+        }
+    };
+
+    let output = input
+        .and_then(|input| read_payloads(&input, from))
+        .and_then(|values| write_payloads(&values, to))
+        .unwrap_or_else(|e| exit(&e));
+
+    let mut stdout = std::io::stdout();
+    if let Err(e) = stdout.write_all(&output).and_then(|_| stdout.flush()) {
+        exit(&format!("Can't write to stdout: {e}"));
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1)
+}
+
+// Atom keys, like the gateway sends
+fn etf_options() -> serde_etf::Options {
+    serde_etf::Options::new().key_style(KeyStyle::Atom)
+}
+
+fn read_payloads(input: &[u8], format: Format) -> Result<Vec<Value>, String> {
+    let messages = if format.zlib {
+        inflate(input)?
+    } else {
+        vec![input.to_vec()]
+    };
+
+    let mut values = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        match format.encoding {
+            Encoding::Etf => {
+                let value = serde_etf::from_slice_with::<Value>(message, &etf_options())
+                    .map_err(|e| format!("Invalid ETF in payload {i}: {e}"))?;
+                values.push(value);
+            }
+            Encoding::Json => {
+                for value in serde_json::Deserializer::from_slice(message).into_iter() {
+                    let value: serde_json::Value =
+                        value.map_err(|e| format!("Invalid JSON in payload {i}: {e}"))?;
+                    values.push(Value::from(value));
+                }
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+fn write_payloads(values: &[Value], format: Format) -> Result<Vec<u8>, String> {
+    if let (Encoding::Etf, false, 2..) = (format.encoding, format.zlib, values.len()) {
+        return Err("etf holds a single term, use zlib-etf for more payloads".to_string());
+    }
+
+    let mut compressor = Compressor::new();
+    let mut output = Vec::new();
+    for value in values {
+        let payload = match format.encoding {
+            Encoding::Etf => serde_etf::to_vec_with(value, &etf_options())
+                .map_err(|e| format!("Can't encode ETF: {e}"))?,
+            Encoding::Json => {
+                let mut json = serde_json::to_vec_pretty(&serde_json::Value::from(value.clone()))
+                    .map_err(|e| format!("Can't encode JSON: {e}"))?;
+                json.push(b'\n');
+                json
+            }
+        };
+
+        if format.zlib {
+            let compressed = compressor
+                .compress(payload)
+                .map_err(|e| format!("Can't compress payload: {e:?}"))?;
+            output.extend_from_slice(&compressed);
+        } else {
+            output.extend_from_slice(&payload);
+        }
+    }
+
+    Ok(output)
+}
+
+// Inflates a whole zlib-stream session, which shares one zlib context. Each
+// message ends with a sync flush, so the session is split there. The suffix can
+// also show up inside compressed data, so only places where the inflater is
+// between blocks count
+fn inflate(input: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let input = match input {
+        [cmf, flg, rest @ ..]
+            if cmf & 0x0f == 8 && flg & 0x20 == 0 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 =>
+        {
+            rest
+        }
+        [] => return Ok(vec![]),
+        _ => return Err("Invalid zlib-stream: bad header".to_string()),
+    };
+
+    // What messages can refer back to, which is all the inflater keeps
+    // between blocks
+    let mut window = Vec::new();
+    let mut messages = Vec::new();
+
+    let mut start = 0;
+    let mut search = 0;
+    while start < input.len() {
+        let suffix = input[search..]
+            .windows(ZLIB_SUFFIX.len())
+            .position(|w| w == ZLIB_SUFFIX)
+            .map(|i| search + i)
+            .ok_or_else(|| format!("Incomplete zlib-stream message {}", messages.len()))?;
+        let end = suffix + ZLIB_SUFFIX.len();
+
+        let mut decompress = resume(&window)?;
+        let message = inflate_message(&mut decompress, &input[start..end])?;
+        if !at_flush(&mut decompress) {
+            search = suffix + 1;
+            continue;
+        }
+
+        window.extend_from_slice(&message);
+        window.drain(..window.len().saturating_sub(WINDOW_SIZE));
+        messages.push(message);
+        start = end;
+        search = end;
+    }
+
+    Ok(messages)
+}
+
+// A raw inflater in the state it was in after `window`, as long as that ended
+// between blocks
+fn resume(window: &[u8]) -> Result<Decompress, String> {
+    let mut decompress = Decompress::new(false);
+    if !window.is_empty() {
+        inflate_message(&mut decompress, &stored_block(window))?;
+    }
+
+    Ok(decompress)
+}
+
+// Whether the inflater is between blocks, by checking that it takes another
+// block. Inside a block, the block's bytes would be read as more data instead
+fn at_flush(decompress: &mut Decompress) -> bool {
+    const PROBE: &[u8] = b"radiance";
+
+    inflate_message(decompress, &stored_block(PROBE)).is_ok_and(|output| output == PROBE)
+}
+
+fn stored_block(data: &[u8]) -> Vec<u8> {
+    let len = u16::try_from(data.len()).expect("Stored blocks hold up to 64 KiB");
+
+    let mut block = vec![0];
+    block.extend_from_slice(&len.to_le_bytes());
+    block.extend_from_slice(&(!len).to_le_bytes());
+    block.extend_from_slice(data);
+    block
+}
+
+fn inflate_message(decompress: &mut Decompress, mut message: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(message.len() * 4);
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(output.capacity().max(1024));
+        }
+
+        let consumed = decompress.total_in();
+        let status = decompress
+            .decompress_vec(message, &mut output, FlushDecompress::Sync)
+            .map_err(|e| format!("Invalid zlib-stream: {e}"))?;
+        message = &message[(decompress.total_in() - consumed) as usize..];
+
+        // Output is only left over if the buffer ran out of space
+        let done = message.is_empty() && output.len() < output.capacity();
+        if done || status == Status::StreamEnd {
+            return Ok(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_etf::etf;
+
+    fn format(s: &str) -> Format {
+        Format::from_str(s).unwrap()
+    }
+
+    // Keys are sorted, since that's the order they come out of JSON in
+    fn payloads() -> Vec<Value> {
+        vec![
+            etf!(#{ "d" => #{ "heartbeat_interval" => 41250 }, "op" => 10 }),
+            etf!(#{ "d" => #{ "guilds" => [], "v" => 9 }, "op" => 0, "s" => 1, "t" => "READY" }),
+            etf!(#{ "d" => nil, "op" => 11 }),
+        ]
+    }
+
+    #[test]
+    fn zlib_stream_round_trip() {
+        for encoding in ["zlib-etf", "zlib-json"] {
+            let session = write_payloads(&payloads(), format(encoding)).unwrap();
+            // Every message ends in the sync flush suffix
+            assert!(session.ends_with(&ZLIB_SUFFIX));
+            assert_eq!(inflate(&session).unwrap().len(), 3);
+            assert_eq!(
+                read_payloads(&session, format(encoding)).unwrap(),
+                payloads()
+            );
+        }
+    }
+
+    #[test]
+    fn large_messages_are_inflated_completely() {
+        let value = etf!(#{ "op" => 0, "d" => vec!["member"; 100_000] });
+
+        let session = write_payloads(&[value.clone(), value.clone()], format("zlib-etf")).unwrap();
+        assert_eq!(
+            read_payloads(&session, format("zlib-etf")).unwrap(),
+            [value.clone(), value]
+        );
+    }
+
+    #[test]
+    fn suffixes_inside_messages_dont_split_them() {
+        // Noise doesn't compress, so it's stored as is, suffix and all
+        let mut seed = 1u32;
+        let mut noise = (0..4096)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 24) as u8
+            })
+            .collect::<Vec<_>>();
+        noise[1000..1004].copy_from_slice(&ZLIB_SUFFIX);
+        let values = [
+            etf!(#{ "d" => Value::Binary(noise), "op" => 0 }),
+            payloads().remove(2),
+        ];
+
+        let session = write_payloads(&values, format("zlib-etf")).unwrap();
+        let first = session
+            .windows(ZLIB_SUFFIX.len())
+            .position(|w| w == ZLIB_SUFFIX)
+            .unwrap();
+        assert!(first + ZLIB_SUFFIX.len() < session.len() - 30);
+        assert_eq!(read_payloads(&session, format("zlib-etf")).unwrap(), values);
+    }
+
+    #[test]
+    fn transcodes_between_etf_and_json() {
+        let value = payloads().remove(1);
+
+        let etf = write_payloads(std::slice::from_ref(&value), format("etf")).unwrap();
+        // Keys are atoms, like the gateway sends them
+        assert_eq!(etf[..2], [131, 116]);
+        assert_eq!(etf[6], 100);
+        let values = read_payloads(&etf, format("etf")).unwrap();
+        let json = write_payloads(&values, format("json")).unwrap();
+        assert_eq!(read_payloads(&json, format("json")).unwrap(), [value]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(Format::from_str("zlib-xml").is_err());
+        assert!(write_payloads(&payloads(), format("etf")).is_err());
+
+        // Cut off in the middle of the second message
+        let session = write_payloads(&payloads(), format("zlib-json")).unwrap();
+        assert!(inflate(&session[..session.len() - 2]).is_err());
+    }
+}
@@ -4,173 +4,283 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::encoding::Encoding;
-use crate::error::GatewayError;
 use crate::event_deserializer::EventDeserializer;
-use crate::events::{EventPayload, RadianceEvent};
-use eetf::{Binary, Term};
-use flate2::{Compress, Compression, FlushCompress};
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
+use log::debug;
+use radiance::compression::Compressor;
+use radiance::encoding::{self, Encoding};
+use radiance::error::GatewayError;
+use radiance::events::{EventPayload, RadianceEvent};
 use serde::de::DeserializeSeed;
-use std::io::Cursor;
-use warp::ws::Message;
-
-pub struct Compressor {
-    compress: Compress,
-    buffer: Box<[u8]>,
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use warp::ws::{Message, WebSocket};
+
+// How many payloads may wait to be sent, before a client is considered too
+// slow and disconnected. Needs to fit a whole replay buffer
+const QUEUE_LIMIT: usize = 2048;
+
+/// Queues payloads for a connection. Cloning it is cheap, so any part of the
+/// server can hold onto one.
+#[derive(Clone)]
+pub struct EventSender {
+    events: mpsc::Sender<EventPayload>,
+    overflow: Arc<Notify>,
+    replaced: Arc<Notify>,
 }
 
-pub struct GatewayConnection {
-    ws: warp::ws::WebSocket,
-    compressor: Compressor,
-    pub encoding: Encoding,
-    compress: bool,
-}
+impl EventSender {
+    pub fn new(limit: usize) -> (Self, mpsc::Receiver<EventPayload>) {
+        let (events, receiver) = mpsc::channel(limit);
 
-impl Compressor {
-    const BUFFER_SIZE: usize = 32 * 1024;
+        let sender = EventSender {
+            events,
+            overflow: Arc::new(Notify::new()),
+            replaced: Arc::new(Notify::new()),
+        };
 
-    pub fn new() -> Compressor {
-        Compressor {
-            compress: Compress::new(Compression::default(), true),
-            buffer: vec![0; Self::BUFFER_SIZE].into_boxed_slice(),
-        }
+        (sender, receiver)
     }
 
-    pub fn compress(&mut self, buffer: Vec<u8>) -> Result<Vec<u8>, GatewayError> {
-        let processed_pre = self.compress.total_in();
-
-        let mut processed = 0;
-
-        // Decompressed message. `Vec::extend_from_slice` efficiently allocates
-        // only what's necessary.
-        let mut compressed = Vec::new();
-
-        loop {
-            let produced_pre = self.compress.total_out();
-
-            // Use Sync to ensure data is flushed to the buffer.
-            self.compress
-                .compress(&buffer[processed..], &mut self.buffer, FlushCompress::Sync)
-                .map_err(|_| GatewayError::CompressionError)?;
+    /// Queues a payload without waiting. A full queue means the client can't
+    /// keep up, so its connection is told to close.
+    pub fn send(&self, payload: EventPayload) -> Result<(), GatewayError> {
+        match self.events.try_send(payload) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.overflow.notify_one();
+                Err(GatewayError::QueueFull)
+            }
+            Err(TrySendError::Closed(_)) => Err(GatewayError::ConnectionClosed),
+        }
+    }
 
-            processed = (self.compress.total_in() - processed_pre)
-                .try_into()
-                .unwrap();
-            let produced = (self.compress.total_out() - produced_pre)
-                .try_into()
-                .unwrap();
+    pub fn same_connection(&self, other: &EventSender) -> bool {
+        self.events.same_channel(&other.events)
+    }
 
-            compressed.extend_from_slice(&self.buffer[..produced]);
+    /// Resolves once a payload didn't fit in the queue.
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
 
-            // Break when message has been fully decompressed.
-            if processed == buffer.len() {
-                break;
-            }
-        }
+    /// Tells the connection another one resumed its session, so it closes.
+    pub fn replace(&self) {
+        self.replaced.notify_one();
+    }
 
-        Ok(compressed)
+    /// Resolves once another connection resumed the session.
+    pub async fn replaced(&self) {
+        self.replaced.notified().await
     }
 }
 
+/// The reading half of a gateway connection. Writing happens on its own task,
+/// which sends whatever is queued on the connection's [`EventSender`].
+pub struct GatewayConnection {
+    stream: SplitStream<WebSocket>,
+    sender: EventSender,
+    close: Option<oneshot::Sender<(u16, &'static str)>>,
+    writer: Option<JoinHandle<()>>,
+    encoding: Encoding,
+    etf_options: serde_etf::Options,
+}
+
 impl GatewayConnection {
-    pub fn new(ws: warp::ws::WebSocket, encoding: Encoding, compress: bool) -> Self {
-        GatewayConnection {
-            ws,
+    pub fn new(ws: WebSocket, encoding: Encoding, compress: bool) -> Self {
+        let (sink, stream) = ws.split();
+        let (sender, events) = EventSender::new(QUEUE_LIMIT);
+        let (close, close_receiver) = oneshot::channel();
+
+        let writer = GatewayWriter {
+            sink,
             compressor: Compressor::new(),
             encoding,
             compress,
+            etf_options: encoding::etf_options(),
+        };
+
+        GatewayConnection {
+            stream,
+            sender,
+            close: Some(close),
+            writer: Some(tokio::spawn(writer.run(events, close_receiver))),
+            encoding,
+            etf_options: encoding::etf_options(),
         }
     }
 
-    pub async fn read_event(&mut self) -> Result<RadianceEvent, GatewayError> {
-        match self.encoding {
-            Encoding::Etf => self.read_event_etf().await,
-            Encoding::Json => self.read_event_json().await,
-        }
+    pub fn sender(&self) -> EventSender {
+        self.sender.clone()
     }
 
-    pub async fn send_event(&mut self, event: EventPayload) -> Result<(), GatewayError> {
+    /// Waits for the next event. Fails once the client is too slow to keep up
+    /// with its queue, or once another connection resumed its session.
+    pub async fn read_event(&mut self) -> Result<RadianceEvent, GatewayError> {
+        let message = loop {
+            let message = tokio::select! {
+                message = self.stream.next() => message
+                    .and_then(|r| r.ok())
+                    .filter(|m| !m.is_close())
+                    .ok_or(GatewayError::ConnectionClosed)?,
+                _ = self.sender.overflowed() => return Err(GatewayError::QueueFull),
+                _ = self.sender.replaced() => return Err(GatewayError::SessionReplaced),
+            };
+
+            // The websocket answers pings itself, they aren't payloads
+            if !message.is_ping() && !message.is_pong() {
+                break message;
+            }
+        };
+
+        let bytes = message.into_bytes();
         match self.encoding {
-            Encoding::Etf => self.send_event_etf(event).await,
-            Encoding::Json => self.send_event_json(event).await,
+            Encoding::Etf => Self::decode_etf(&bytes, &self.etf_options),
+            Encoding::Json => Self::decode_json(&bytes),
         }
     }
 
-    async fn read_event_common(&mut self) -> Result<Vec<u8>, GatewayError> {
-        let message = self
-            .ws
-            .next()
-            .await
-            .and_then(|r| r.ok())
-            .ok_or(GatewayError::ConnectionClosed)?;
+    pub fn send_event(&self, event: EventPayload) -> Result<(), GatewayError> {
+        self.sender.send(event)
+    }
 
-        Ok(message.into_bytes())
+    /// Closes the connection right away, dropping anything that's still
+    /// queued. Dropping the connection instead sends the queue first.
+    pub async fn close(&mut self, code: u16, reason: &'static str) {
+        if let Some(close) = self.close.take() {
+            let _ = close.send((code, reason));
+        }
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.await;
+        }
     }
 
-    async fn read_event_json(&mut self) -> Result<RadianceEvent, GatewayError> {
-        let json = String::from_utf8(self.read_event_common().await?)
-            .map_err(|_| GatewayError::InvalidEncoding)?;
+    fn decode_json(json: &[u8]) -> Result<RadianceEvent, GatewayError> {
+        let json = std::str::from_utf8(json).map_err(|_| GatewayError::InvalidEncoding)?;
 
         let deserializer =
-            EventDeserializer::from_json(&*json).ok_or(GatewayError::InvalidEncoding)?;
-        let mut json_deserializer = serde_json::Deserializer::from_str(&*json);
-        let event = deserializer.deserialize(&mut json_deserializer).unwrap();
+            EventDeserializer::from_json(json).ok_or(GatewayError::InvalidEncoding)?;
+        if !deserializer.is_known_opcode() {
+            return Err(GatewayError::UnknownOpcode);
+        }
+        let mut json_deserializer = serde_json::Deserializer::from_str(json);
+        let event = deserializer
+            .deserialize(&mut json_deserializer)
+            .map_err(|_| GatewayError::InvalidEncoding)?;
+        json_deserializer
+            .end()
+            .map_err(|_| GatewayError::InvalidEncoding)?;
 
         Ok(event)
     }
 
-    async fn read_event_etf(&mut self) -> Result<RadianceEvent, GatewayError> {
-        let etf = self.read_event_common().await?;
-
-        let Term::Map(term) =
-            Term::decode(Cursor::new(&etf)).map_err(|_| GatewayError::InvalidEncoding)?
-        else {
-            Err(GatewayError::IncompleteData)?
-        };
+    fn decode_etf(etf: &[u8], options: &serde_etf::Options) -> Result<RadianceEvent, GatewayError> {
+        let deserializer = EventDeserializer::from_etf(etf, options).map_err(Self::etf_error)?;
+        if !deserializer.is_known_opcode() {
+            return Err(GatewayError::UnknownOpcode);
+        }
+        let mut etf_deserializer =
+            serde_etf::Decoder::from_slice_with(etf, options).map_err(Self::etf_error)?;
+        let event = deserializer
+            .deserialize(&mut etf_deserializer)
+            .map_err(Self::etf_error)?;
+        etf_deserializer.end().map_err(Self::etf_error)?;
 
-        let op_term = Term::Binary(Binary::from("op".as_bytes()));
-        let event_type_term = Term::Binary(Binary::from("t".as_bytes()));
+        Ok(event)
+    }
 
-        let Term::FixInteger(op) = term.map.get(&op_term).ok_or(GatewayError::IncompleteData)?
-        else {
-            Err(GatewayError::IncompleteData)?
-        };
+    fn etf_error(error: serde_etf::Error) -> GatewayError {
+        match error.inner() {
+            serde_etf::Error::LimitExceeded(_) => GatewayError::LimitExceeded,
+            serde_etf::Error::UnexpectedEof => GatewayError::IncompleteData,
+            _ => GatewayError::InvalidEncoding,
+        }
+    }
+}
 
-        let event_type_string = term.map.get(&event_type_term).and_then(|t| match t {
-            Term::Atom(a) => Some(a.name.clone()),
-            _ => None,
-        });
+struct GatewayWriter {
+    sink: SplitSink<WebSocket, Message>,
+    compressor: Compressor,
+    encoding: Encoding,
+    compress: bool,
+    etf_options: serde_etf::Options,
+}
 
-        let event_type = event_type_string.as_ref().map(|s| s.as_str());
+impl GatewayWriter {
+    async fn run(
+        mut self,
+        mut events: mpsc::Receiver<EventPayload>,
+        mut close: oneshot::Receiver<(u16, &'static str)>,
+    ) {
+        loop {
+            tokio::select! {
+                biased;
+
+                close = &mut close => {
+                    match close {
+                        Ok((code, reason)) => {
+                            let _ = self.sink.send(Message::close_with(code, reason)).await;
+                        }
+                        // The connection was dropped, so the queue is flushed
+                        Err(_) => {
+                            events.close();
+                            while let Some(event) = events.recv().await {
+                                if self.send_event(event).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+
+                    break;
+                }
+                event = events.recv() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+
+                    if let Err(e) = self.send_event(event).await {
+                        debug!("Can't send event: {e:?}");
+
+                        if let Some((code, reason)) = e.close_frame() {
+                            let _ = self.sink.send(Message::close_with(code, reason)).await;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
 
-        let deserializer = EventDeserializer::new(op.value as u8, event_type);
-        let etf_deserializer = serde_etf::Deserializer::from_term(Term::Map(term));
-        let event = deserializer.deserialize(etf_deserializer).unwrap();
+        let _ = self.sink.close().await;
+    }
 
-        Ok(event)
+    async fn send_event(&mut self, event: EventPayload) -> Result<(), GatewayError> {
+        match self.encoding {
+            Encoding::Etf => self.send_event_etf(event).await,
+            Encoding::Json => self.send_event_json(event).await,
+        }
     }
 
     async fn send_event_common(&mut self, payload: Vec<u8>) -> Result<(), GatewayError> {
-        if self.compress {
-            let compressed = self.compressor.compress(payload)?;
-
-            self.ws.send(Message::binary(compressed))
+        let message = if self.compress {
+            Message::binary(self.compressor.compress(payload)?)
         } else {
-            // fixme: unsafe
-            self.ws.send(Message::text(unsafe {
-                String::from_utf8_unchecked(payload)
-            }))
-        }
-        .await
-        .map_err(|_| GatewayError::ConnectionClosed)?;
+            match self.encoding {
+                // ETF isn't text, so it goes out as is
+                Encoding::Etf => Message::binary(payload),
+                Encoding::Json => Message::text(
+                    String::from_utf8(payload).map_err(|_| GatewayError::EncodeError)?,
+                ),
+            }
+        };
 
-        self.ws
-            .flush()
+        self.sink
+            .send(message)
             .await
-            .map_err(|_| GatewayError::ConnectionClosed)?;
-
-        Ok(())
+            .map_err(|_| GatewayError::ConnectionClosed)
     }
 
     async fn send_event_json(&mut self, event: EventPayload) -> Result<(), GatewayError> {
@@ -179,11 +289,125 @@ impl GatewayConnection {
     }
 
     async fn send_event_etf(&mut self, event: EventPayload) -> Result<(), GatewayError> {
-        let payload = serde_etf::to_term(&event).map_err(|_| GatewayError::EncodeError)?;
-        let mut buf = Vec::new();
-        payload
-            .encode(&mut buf)
+        let payload = serde_etf::to_vec_with(&event, &self.etf_options)
             .map_err(|_| GatewayError::EncodeError)?;
-        self.send_event_common(buf).await
+        self.send_event_common(payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_etf::etf;
+    use twilight_model::gateway::event::Event;
+    use warp::Filter;
+
+    fn decode_etf(value: serde_etf::Value) -> Result<RadianceEvent, GatewayError> {
+        let options = encoding::etf_options();
+        let etf = serde_etf::to_vec_with(&value, &options).unwrap();
+
+        GatewayConnection::decode_etf(&etf, &options)
+    }
+
+    #[test]
+    fn decodes_events() {
+        let heartbeat = RadianceEvent::Twilight(Event::GatewayHeartbeat(3));
+
+        assert_eq!(
+            GatewayConnection::decode_json(br#"{"op":1,"d":3}"#),
+            Ok(heartbeat.clone())
+        );
+        assert_eq!(decode_etf(etf!(#{ "op" => 1, "d" => 3 })), Ok(heartbeat));
+
+        let request = GatewayConnection::decode_json(br#"{"op":8,"d":{"guild_id":"1","limit":0}}"#);
+        let Ok(RadianceEvent::RequestGuildMembers(request)) = request else {
+            panic!("Expected a member request, got {request:?}");
+        };
+        assert_eq!((request.guild_id.get(), request.query), (1, None));
+    }
+
+    #[test]
+    fn rejects_unknown_opcodes() {
+        for op in [5, 12, 42] {
+            let json = format!(r#"{{"op":{op},"d":null}}"#);
+
+            assert_eq!(
+                GatewayConnection::decode_json(json.as_bytes()),
+                Err(GatewayError::UnknownOpcode)
+            );
+            assert_eq!(
+                decode_etf(etf!(#{ "op" => op, "d" => nil })),
+                Err(GatewayError::UnknownOpcode)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        for json in [
+            &b"\xff"[..],
+            b"{}",
+            br#"{"op":1,"d":"three"}"#,
+            br#"{"op":1,"d":3}x"#,
+        ] {
+            assert_eq!(
+                GatewayConnection::decode_json(json),
+                Err(GatewayError::InvalidEncoding)
+            );
+        }
+
+        assert_eq!(
+            decode_etf(etf!(#{ "op" => 1, "d" => "three" })),
+            Err(GatewayError::InvalidEncoding)
+        );
+        assert_eq!(
+            GatewayConnection::decode_etf(&[131, 116], &encoding::etf_options()),
+            Err(GatewayError::IncompleteData)
+        );
+        assert_eq!(
+            decode_etf(etf!(#{ "op" => 1, "d" => vec![0; 5000] })),
+            Err(GatewayError::LimitExceeded)
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_pings() {
+        // Acknowledges the first event it reads, or closes the connection
+        let gateway = warp::ws().map(|ws: warp::ws::Ws| {
+            ws.on_upgrade(|socket| async {
+                let mut connection = GatewayConnection::new(socket, Encoding::Json, false);
+                if connection.read_event().await.is_ok() {
+                    let _ = connection.send_event(EventPayload::heartbeat_ack());
+                }
+            })
+        });
+
+        let mut client = warp::test::ws().handshake(gateway).await.unwrap();
+        client.send(Message::ping(vec![1])).await;
+        client.send(Message::pong(vec![2])).await;
+        client.send_text(r#"{"op":1,"d":3}"#).await;
+
+        let reply = loop {
+            let message = client.recv().await.unwrap();
+            if !message.is_pong() {
+                break message;
+            }
+        };
+        assert_eq!(reply.to_str(), Ok(r#"{"op":11}"#));
+    }
+
+    #[tokio::test]
+    async fn sends_etf_as_binary() {
+        let gateway = warp::ws().map(|ws: warp::ws::Ws| {
+            ws.on_upgrade(|socket| async {
+                let connection = GatewayConnection::new(socket, Encoding::Etf, false);
+                let _ = connection.send_event(EventPayload::heartbeat_ack());
+            })
+        });
+
+        let mut client = warp::test::ws().handshake(gateway).await.unwrap();
+        let message = client.recv().await.unwrap();
+        assert!(message.is_binary());
+        assert_eq!(message.as_bytes()[..2], [131, 116]);
     }
 }
@@ -4,7 +4,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::events::{Identify, PresenceUpdate, RadianceEvent, Resume, VoiceStateUpdate};
+use radiance::events::{
+    Identify, LazyRequest, PresenceUpdate, RadianceEvent, RequestGuildMembers, Resume,
+    VoiceStateUpdate,
+};
 use serde::de::value::U8Deserializer;
 use serde::de::{
     DeserializeSeed, Error, IgnoredAny, IntoDeserializer, MapAccess, Unexpected, Visitor,
@@ -17,6 +20,10 @@ use twilight_model::gateway::event::{DispatchEventWithTypeDeserializer, Event, G
 use twilight_model::gateway::payload::incoming::Hello;
 use twilight_model::gateway::OpCode;
 
+// Sent by the client to subscribe to member lists and the like. Twilight
+// doesn't know about it
+const LAZY_REQUEST: u8 = 14;
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
 #[serde(field_identifier, rename_all = "lowercase")]
 enum Field {
@@ -26,6 +33,17 @@ enum Field {
     T,
 }
 
+// Just the fields of an ETF payload, that are needed to pick how `d` is
+// deserialized
+#[derive(Deserialize)]
+struct EtfHeader<'a> {
+    op: u8,
+    #[serde(borrow, default)]
+    t: Option<Cow<'a, str>>,
+    #[serde(default)]
+    s: Option<u64>,
+}
+
 pub struct EventDeserializer<'a> {
     event_type: Option<Cow<'a, str>>,
     op: u8,
@@ -55,6 +73,24 @@ impl<'a> EventDeserializer<'a> {
         })
     }
 
+    /// Create a gateway event deserializer by decoding the ETF payload's
+    /// opcode and dispatch event type.
+    ///
+    /// Keys may be atoms or binaries. The event type is borrowed from the
+    /// input, so this doesn't allocate.
+    pub fn from_etf(
+        input: &'a [u8],
+        options: &serde_etf::Options,
+    ) -> Result<Self, serde_etf::Error> {
+        let header = serde_etf::from_slice_with::<EtfHeader>(input, options)?;
+
+        Ok(Self {
+            event_type: header.t,
+            op: header.op,
+            sequence: header.s,
+        })
+    }
+
     /// Create a deserializer with an owned event type.
     ///
     /// This is necessary when using a mutable deserialization library such as
@@ -85,6 +121,11 @@ impl<'a> EventDeserializer<'a> {
         self.op
     }
 
+    /// Whether the payload's opcode is one that can be deserialized.
+    pub fn is_known_opcode(&self) -> bool {
+        OpCode::from(self.op).is_some() || self.op == LAZY_REQUEST
+    }
+
     /// Sequence of the payload.
     ///
     /// May only be available if the deserializer was created via
@@ -209,6 +250,14 @@ impl<'de> Visitor<'de> for EventVisitor<'_> {
             "RECONNECT",
         ];
 
+        if self.0 == LAZY_REQUEST {
+            let request = Self::field::<LazyRequest, _>(&mut map, Field::D)?;
+
+            Self::ignore_all(&mut map)?;
+
+            return Ok(RadianceEvent::LazyRequest(request));
+        }
+
         let op_deser: U8Deserializer<V::Error> = self.0.into_deserializer();
 
         let op = OpCode::deserialize(op_deser).ok().ok_or_else(|| {
@@ -296,7 +345,7 @@ impl<'de> Visitor<'de> for EventVisitor<'_> {
 
                 Self::ignore_all(&mut map)?;
 
-                RadianceEvent::Identify(identify)
+                RadianceEvent::Identify(Box::new(identify))
             }
             OpCode::Reconnect => {
                 Self::ignore_all(&mut map)?;
@@ -304,10 +353,11 @@ impl<'de> Visitor<'de> for EventVisitor<'_> {
                 RadianceEvent::Twilight(Event::from(GatewayEvent::Reconnect))
             }
             OpCode::RequestGuildMembers => {
-                return Err(V::Error::unknown_variant(
-                    "RequestGuildMembers",
-                    VALID_OPCODES,
-                ))
+                let request = Self::field::<RequestGuildMembers, _>(&mut map, Field::D)?;
+
+                Self::ignore_all(&mut map)?;
+
+                RadianceEvent::RequestGuildMembers(request)
             }
             OpCode::Resume => {
                 let resume = Self::field::<Resume, _>(&mut map, Field::D)?;
@@ -1,13 +0,0 @@
-// radiance (c) Nikolas Wipper 2024
-
-/* This Source Code Form is subject to the terms of the Mozilla Public
- * License, v. 2.0. If a copy of the MPL was not distributed with this
- * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct MergedPresences {
-    pub friends: Vec<()>,
-    pub guilds: Vec<Vec<()>>,
-}
@@ -0,0 +1,131 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::time::{Duration, Instant};
+
+// What Discord sends
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(41250);
+// Leeway for latency, on top of the interval
+const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Copy, Clone, Debug)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    pub grace: Duration,
+}
+
+impl HeartbeatConfig {
+    /// Reads the interval in milliseconds from `RADIANCE_HEARTBEAT_INTERVAL`,
+    /// falling back to Discord's.
+    pub fn from_env() -> Self {
+        let interval = std::env::var("RADIANCE_HEARTBEAT_INTERVAL")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INTERVAL);
+
+        HeartbeatConfig {
+            interval,
+            ..Self::default()
+        }
+    }
+
+    // How long a client may stay silent before identifying or heartbeating
+    pub fn timeout(&self) -> Duration {
+        self.interval + self.grace
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            interval: DEFAULT_INTERVAL,
+            grace: DEFAULT_GRACE,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum HeartbeatAction {
+    // Ask the client to heartbeat right away with an op 1
+    Request,
+    TimedOut,
+}
+
+/// Tracks when the client has to heartbeat next. Once the interval passes
+/// without one, the client is asked for a heartbeat, and after the grace
+/// period as well, the connection is considered dead.
+pub struct Heartbeat {
+    config: HeartbeatConfig,
+    last: Instant,
+    requested: bool,
+}
+
+impl Heartbeat {
+    pub fn new(config: HeartbeatConfig, now: Instant) -> Self {
+        Heartbeat {
+            config,
+            last: now,
+            requested: false,
+        }
+    }
+
+    pub fn received(&mut self, now: Instant) {
+        self.last = now;
+        self.requested = false;
+    }
+
+    pub fn deadline(&self) -> Instant {
+        if self.requested {
+            self.last + self.config.timeout()
+        } else {
+            self.last + self.config.interval
+        }
+    }
+
+    /// Called once the deadline has passed.
+    pub fn expired(&mut self) -> HeartbeatAction {
+        if self.requested {
+            HeartbeatAction::TimedOut
+        } else {
+            self.requested = true;
+            HeartbeatAction::Request
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: HeartbeatConfig = HeartbeatConfig {
+        interval: Duration::from_secs(40),
+        grace: Duration::from_secs(5),
+    };
+
+    #[test]
+    fn requests_then_times_out() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(CONFIG, start);
+
+        assert_eq!(heartbeat.deadline(), start + CONFIG.interval);
+        assert_eq!(heartbeat.expired(), HeartbeatAction::Request);
+        assert_eq!(heartbeat.deadline(), start + CONFIG.timeout());
+        assert_eq!(heartbeat.expired(), HeartbeatAction::TimedOut);
+    }
+
+    #[test]
+    fn heartbeats_push_the_deadline() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(CONFIG, start);
+        assert_eq!(heartbeat.expired(), HeartbeatAction::Request);
+
+        let later = start + Duration::from_secs(42);
+        heartbeat.received(later);
+        assert_eq!(heartbeat.deadline(), later + CONFIG.interval);
+        assert_eq!(heartbeat.expired(), HeartbeatAction::Request);
+    }
+}
@@ -7,37 +7,100 @@
 #![feature(async_closure)]
 
 mod connection;
-mod encoding;
-mod error;
 mod event_deserializer;
-mod events;
+mod heartbeat;
+mod member_list;
+mod members;
+mod permissions;
+mod presence;
+mod ratelimit;
+mod sessions;
+mod voice;
 
 use crate::connection::GatewayConnection;
-use crate::encoding::Encoding;
-use crate::error::GatewayError;
-use crate::events::models::{
-    Authentication, ClientInfo, Consent, MergedPresences, NotificationSettings, Personalization,
-    ReadState, Session, Status, User, UserGuildSettings,
-};
-use crate::events::{EventPayload, IntoPayload, RadianceEvent, Ready, ReadySupplemental};
-use log::debug;
+use crate::heartbeat::{Heartbeat, HeartbeatAction, HeartbeatConfig};
+use crate::member_list::{MemberList, MemberLists};
+use crate::presence::{PresenceService, UserPresence};
+use crate::ratelimit::RateLimit;
+use crate::sessions::{ResumeError, SessionId, SessionRegistry};
+use crate::voice::{VoiceConfig, VoiceStates};
+use log::{debug, warn};
+use radiance::bus::{self, BusEvent, LocalBus, Topic};
+use radiance::encoding::Encoding;
+use radiance::error::GatewayError;
+use radiance::events::models::{ClientInfo, Presence, Relationship};
+use radiance::events::{EventPayload, IntoPayload, RadianceEvent, Ready, UserPresenceUpdate};
+use radiance::store::{AccountStore, AccountView, StoredGuild};
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use twilight_model::gateway::event::Event;
-use twilight_model::gateway::payload::incoming::Hello;
+use twilight_model::gateway::payload::incoming::{Hello, VoiceStateUpdate};
+use twilight_model::id::marker::UserMarker;
 use twilight_model::id::Id;
+use twilight_model::voice::VoiceState;
 use warp::Filter;
 
+// Gateway versions the payloads are modelled after
+const API_VERSIONS: &[&str] = &["9"];
+
+// Everything connections share
+#[derive(Clone)]
+pub struct GatewayState {
+    registry: SessionRegistry,
+    heartbeat: HeartbeatConfig,
+    store: AccountStore,
+    member_lists: MemberLists,
+    presences: PresenceService,
+    voice: VoiceStates,
+    voice_config: VoiceConfig,
+}
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
 
+    let store = AccountStore::load(&AccountStore::path()).expect("Can't load the account store");
+
+    // `gateway token <user id>` prints a token to log in with
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [command, user_id] = args.as_slice() {
+        if command == "token" {
+            let user_id = user_id.parse().expect("Invalid user ID");
+            match store.token(user_id) {
+                Some(token) => println!("{token}"),
+                None => eprintln!("The account store has no secret, or still the example's"),
+            }
+            return;
+        }
+    }
+
+    let state = GatewayState {
+        registry: SessionRegistry::new(),
+        heartbeat: HeartbeatConfig::from_env(),
+        store,
+        member_lists: MemberLists::default(),
+        presences: PresenceService::default(),
+        voice: VoiceStates::default(),
+        voice_config: VoiceConfig::from_env(),
+    };
+
+    // Events published by rest come in over the socket
+    let bus = LocalBus::new();
+    bus::listen(&bus::socket_path(), bus.clone()).expect("Can't listen for the event bus");
+    tokio::spawn(fan_out(bus.subscribe(), state.registry.clone()));
+
     let gateway = warp::any()
         .and(warp::ws())
         .and(warp::query::<HashMap<String, String>>())
-        .map(|ws: warp::ws::Ws, p: HashMap<String, String>| {
-            ws.on_upgrade(async move |ws| gateway(ws, p).await)
-        });
+        .and(warp::any().map(move || state.clone()))
+        .map(
+            |ws: warp::ws::Ws, p: HashMap<String, String>, state: GatewayState| {
+                ws.on_upgrade(async move |ws| gateway(ws, p, state).await)
+            },
+        );
 
     warp::serve(warp::get().and(gateway))
         .tls()
@@ -47,7 +110,21 @@ async fn main() {
         .await;
 }
 
-pub async fn gateway(ws: warp::ws::WebSocket, p: HashMap<String, String>) {
+// Dispatches events from the bus to every session subscribed to them
+async fn fan_out(mut events: broadcast::Receiver<BusEvent>, registry: SessionRegistry) {
+    loop {
+        match events.recv().await {
+            Ok(event) => match event.event() {
+                Ok(dispatch) => registry.publish(event.topic, &dispatch),
+                Err(e) => warn!("Invalid {} event on the bus: {e}", event.t),
+            },
+            Err(RecvError::Lagged(skipped)) => warn!("Fan out skipped {skipped} events"),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+pub async fn gateway(ws: warp::ws::WebSocket, p: HashMap<String, String>, state: GatewayState) {
     let zlib = p
         .get("compress")
         .map(|c| c == "zlib-stream")
@@ -60,169 +137,369 @@ pub async fn gateway(ws: warp::ws::WebSocket, p: HashMap<String, String>) {
     {
         debug!("Opening WebSocket ({encoding}, zlib: {zlib})");
 
-        let connection = GatewayConnection::new(ws, encoding, zlib);
+        let mut connection = GatewayConnection::new(ws, encoding, zlib);
+
+        let res = match p.get("v") {
+            Some(v) if !API_VERSIONS.contains(&v.as_str()) => Err(GatewayError::InvalidApiVersion),
+            _ => failing_gateway(&mut connection, &state).await,
+        };
+
+        if let Err(e) = res {
+            debug!("Closing WebSocket: {e:?}");
 
-        let res = failing_gateway(connection).await;
-        res.unwrap();
+            if let Some((code, reason)) = e.close_frame() {
+                connection.close(code, reason).await;
+            }
+        }
     }
 }
 
-async fn failing_gateway(mut connection: GatewayConnection) -> Result<(), GatewayError> {
+async fn failing_gateway(
+    connection: &mut GatewayConnection,
+    state: &GatewayState,
+) -> Result<(), GatewayError> {
+    let registry = &state.registry;
+
+    // Only counts dispatches, which the session takes care of
     let mut sequence = 0;
 
-    // fixme: should the interval be dynamically chosen?
     let hello = Event::GatewayHello(Hello {
-        heartbeat_interval: 41250,
+        heartbeat_interval: state.heartbeat.interval.as_millis() as u64,
     });
 
-    connection
-        .send_event(hello.into_payload(&mut sequence))
-        .await?;
+    connection.send_event(hello.into_payload(&mut sequence))?;
 
     debug!("Sent hello");
 
-    let identify = match connection.read_event().await? {
-        RadianceEvent::Identify(identify) => identify,
-        RadianceEvent::Resume(_) => {
-            let invalid_session = Event::GatewayInvalidateSession(false);
+    // Clients that never identify are dropped like ones that stop heartbeating
+    let first = tokio::time::timeout(state.heartbeat.timeout(), connection.read_event())
+        .await
+        .map_err(|_| GatewayError::SessionTimeout)??;
+
+    let session_id = match first {
+        RadianceEvent::Identify(identify) => {
+            debug!("Received identify");
+
+            let view = state
+                .store
+                .login(&identify.token)
+                .ok_or(GatewayError::AuthenticationFailed)?;
+            let client_info = ClientInfo {
+                client: "web".to_string(),
+                os: identify.properties.os,
+                version: 0,
+            };
 
-            connection
-                .send_event(invalid_session.into_payload(&mut sequence))
-                .await?;
-            return Ok(());
+            let session_id = registry.register(
+                &identify.token,
+                view.account.user.id,
+                client_info,
+                identify.presence,
+                connection.sender(),
+            );
+            send_ready(state, session_id, &view);
+            refresh_presence(state, view.account.user.id);
+
+            session_id
         }
+        RadianceEvent::Resume(resume) => match registry.resume(&resume, connection.sender()) {
+            Ok(()) => {
+                debug!("Resumed session {}", resume.session_id);
+                if let Some(user) = registry.user(resume.session_id) {
+                    refresh_presence(state, user);
+                }
+
+                resume.session_id
+            }
+            Err(ResumeError::InvalidToken) => return Err(GatewayError::AuthenticationFailed),
+            Err(ResumeError::InvalidSequence) => return Err(GatewayError::InvalidSequence),
+            Err(e) => {
+                debug!("Can't resume session {}: {e:?}", resume.session_id);
+
+                let invalid_session = Event::GatewayInvalidateSession(false);
+
+                connection.send_event(invalid_session.into_payload(&mut sequence))?;
+                return Ok(());
+            }
+        },
         _ => {
-            return Err(GatewayError::UnexpectedPayload);
+            return Err(GatewayError::NotAuthenticated);
         }
     };
 
-    debug!("Received identify");
+    // The session stays resumable, however the connection ends
+    let res = heartbeat(connection, state, session_id).await;
+    // Voice needs a gateway connection, so losing it ends voice too. Unless
+    // another connection resumed the session and took over
+    if registry.disconnect(session_id, &connection.sender()) {
+        for voice_state in state.voice.disconnect(&session_id.to_string()) {
+            publish_voice_state(registry, voice_state);
+        }
+    }
+    if let Some(user) = registry.user(session_id) {
+        refresh_presence(state, user);
+    }
 
-    let session = Session {
-        activities: vec![],
-        client_info: ClientInfo {
-            client: "web".to_string(),
-            os: identify.properties.os,
-            version: 0,
-        },
-        sesion_id: Id::new(1),
-        status: Status::Online,
-    };
+    res
+}
 
-    let ready = Ready {
-        analytics_token: "OTY3NDc4MTA3NTUwMzg4MjM1.gBFnffeJgsNj6npFfnYxcabuolk".to_string(),
-        api_code_version: 1,
-        auth: Authentication {
-            authenticator_types: vec![],
-        },
-        auth_session_id_hash: "cJ9xGK8GACC1G+HofFNJHb6VdVx95Uy1/ycpFy5Y9Sc=".to_string(),
-        connected_accounts: vec![],
-        consents: Consent {
-            personalization: Personalization { consented: false },
-        },
-        country_code: "DE".to_string(),
-        current_location: vec!["DE".to_string(), "DE:BE".to_string()],
-        experiments: vec![],
-        friend_suggestion_count: 0,
-        geo_ordered_rtc_regions: vec![
-            "frankfurt".to_string(),
-            "frankfurt-two".to_string(),
-            "rotterdam".to_string(),
-            "stockholm".to_string(),
-            "milan".to_string(),
-        ],
-        guild_experiments: vec![],
-        guild_join_requests: vec![],
-        guilds: vec![],
-        merged_members: vec![],
-        notification_settings: NotificationSettings { flags: 0 },
-        private_channels: vec![],
-        read_state: ReadState {
-            entries: vec![],
-            partial: false,
-            version: 0,
-        },
-        relationships: vec![],
-        required_action: None,
-        resume_gateway_url: "wss://gateway.discord.gg".to_string(),
-        session_id: Id::new(1),
-        session_type: "normal".to_string(),
-        sessions: vec![session.clone()],
-        tutorial: None,
-        user: User {
-            accent_color: None,
-            avatar: None,
-            avatar_decoration_data: None,
-            banner: None,
-            banner_color: None,
-            bio: "".to_string(),
-            desktop: false,
-            discriminator: 0,
-            email: "mail@example.com".to_string(),
-            flags: 0,
-            global_name: None,
-            id: Id::new(1),
-            mfa_enabled: false,
-            mobile: false,
-            nsfw_allowed: false,
-            phone: None,
-            premium: false,
-            premium_type: 0,
-            pronouns: "".to_string(),
-            purchased_flags: 0,
-            username: "Person".to_string(),
-            verified: true,
-        },
-        user_guild_settings: UserGuildSettings {
-            entries: vec![],
-            partial: false,
-            version: 0,
-        },
-        user_settings_proto: "".to_string(),
-        users: vec![],
-        v: 9,
-    };
+// Everything a session hears about
+fn topics(ready: &Ready) -> Vec<Topic> {
+    let guilds = ready.guilds.iter().flat_map(|guild| {
+        let channels = guild.channels.iter().chain(&guild.threads);
+
+        std::iter::once(Topic::Guild(guild.id))
+            .chain(channels.map(|channel| Topic::Channel(channel.id)))
+    });
+    let private_channels = ready
+        .private_channels
+        .iter()
+        .map(|channel| Topic::Channel(channel.id));
 
-    connection
-        .send_event(ready.into_payload(&mut sequence))
-        .await?;
+    std::iter::once(Topic::User(ready.user.id))
+        .chain(guilds)
+        .chain(private_channels)
+        .collect()
+}
+
+fn send_ready(state: &GatewayState, session_id: SessionId, view: &AccountView) {
+    let registry = &state.registry;
+    let sessions = registry.sessions(view.account.user.id);
+
+    let ready = view.ready(session_id, sessions);
+    for topic in topics(&ready) {
+        registry.subscribe(session_id, topic);
+    }
+    registry.dispatch(session_id, ready);
 
     debug!("Sent ready");
 
-    let ready_supplemental = ReadySupplemental {
-        disclose: vec!["pomelo".to_string()],
-        game_invites: vec![],
-        guilds: vec![],
-        lazy_private_channels: vec![],
-        merged_members: vec![],
-        merged_presences: MergedPresences {
-            friends: vec![],
-            guilds: vec![],
-        },
+    let supplemental = view.ready_supplemental(
+        |guild_id| state.voice.guild(guild_id),
+        |user| state.presences.get(user).member_presence(user),
+    );
+    registry.dispatch(session_id, supplemental);
+
+    debug!("Sent ready supplemental");
+}
+
+fn publish_voice_state(registry: &SessionRegistry, voice_state: VoiceState) {
+    if let Some(guild_id) = voice_state.guild_id {
+        let update = Event::VoiceStateUpdate(Box::new(VoiceStateUpdate(voice_state)));
+        registry.publish(Topic::Guild(guild_id), &update);
+    }
+}
+
+// Tells `user`'s sessions about each other, and everyone else about the
+// user's presence, if it changed
+fn refresh_presence(state: &GatewayState, user: Id<UserMarker>) {
+    let registry = &state.registry;
+
+    registry.publish(Topic::User(user), &registry.sessions(user));
+
+    let Some(presence) = state.presences.update(user, &registry.presences(user)) else {
+        return;
     };
+    debug!("{user} is now {:?}", presence.status);
+
+    publish_presence(state, user, &presence);
+    refresh_member_lists(state, user);
+}
 
-    connection
-        .send_event(ready_supplemental.into_payload(&mut sequence))
-        .await?;
+// Friends get the presence on its own, guilds with their ID
+fn publish_presence(state: &GatewayState, user: Id<UserMarker>, presence: &UserPresence) {
+    let Some(account) = state.store.account(user) else {
+        return;
+    };
+    let update = |guild_id| UserPresenceUpdate {
+        presence: presence.member_presence(user),
+        guild_id,
+    };
 
-    debug!("Sent ready supplemental");
+    for relationship in &account.relationships {
+        if relationship.kind == Relationship::FRIEND {
+            let friend = Topic::User(relationship.user_id);
+            state.registry.publish(friend, &update(None));
+        }
+    }
+    for &guild_id in &account.guilds {
+        state
+            .registry
+            .publish(Topic::Guild(guild_id), &update(Some(guild_id)));
+    }
+}
 
-    let sessions_replace = vec![session.clone()];
+fn member_list(state: &GatewayState, guild: &StoredGuild) -> MemberList {
+    MemberList::new(&guild.guild.roles, &guild.members, |user| {
+        state.presences.get(user)
+    })
+}
 
-    connection
-        .send_event(sessions_replace.into_payload(&mut sequence))
-        .await?;
+// Sends subscribers the changes to the member lists `user` is on
+fn refresh_member_lists(state: &GatewayState, user: Id<UserMarker>) {
+    let Some(account) = state.store.account(user) else {
+        return;
+    };
 
-    debug!("Sent sessions replace");
+    for &guild_id in &account.guilds {
+        let Some(guild) = state.store.guild(user, guild_id) else {
+            continue;
+        };
+
+        let updates = state
+            .member_lists
+            .refresh(guild_id, member_list(state, guild), |session| {
+                state.registry.user(session).is_some()
+            });
+        for (session, update) in updates {
+            state.registry.dispatch(session, update);
+        }
+    }
+}
+
+async fn heartbeat(
+    connection: &mut GatewayConnection,
+    state: &GatewayState,
+    session_id: SessionId,
+) -> Result<(), GatewayError> {
+    let registry = &state.registry;
+    let mut heartbeat = Heartbeat::new(state.heartbeat, Instant::now());
+    let mut rate_limit = RateLimit::gateway(Instant::now());
+    let mut member_requests = RateLimit::member_requests(Instant::now());
 
     loop {
-        let event = connection.read_event().await?;
+        let deadline = tokio::time::Instant::from_std(heartbeat.deadline());
+
+        let event = tokio::select! {
+            event = connection.read_event() => event?,
+            _ = tokio::time::sleep_until(deadline) => {
+                match heartbeat.expired() {
+                    HeartbeatAction::Request => {
+                        let mut sequence = registry.sequence(session_id).unwrap_or_default();
+                        let request = Event::GatewayHeartbeat(u64::from(sequence));
+
+                        connection.send_event(request.into_payload(&mut sequence))?;
+                        debug!("Requested heartbeat");
+                    }
+                    HeartbeatAction::TimedOut => return Err(GatewayError::SessionTimeout),
+                }
+
+                continue;
+            }
+        };
+
+        if !rate_limit.check(Instant::now()) {
+            return Err(GatewayError::RateLimited);
+        }
 
         match event {
+            RadianceEvent::Identify(_) | RadianceEvent::Resume(_) => {
+                return Err(GatewayError::AlreadyAuthenticated);
+            }
             RadianceEvent::Twilight(Event::GatewayHeartbeat(_)) => {
-                connection.send_event(EventPayload::heartbeat_ack()).await?;
+                heartbeat.received(Instant::now());
+                connection.send_event(EventPayload::heartbeat_ack())?;
                 debug!("Heartbeat");
             }
+            RadianceEvent::RequestGuildMembers(request) => {
+                if !member_requests.check(Instant::now()) {
+                    debug!("Ignoring member request for {}", request.guild_id);
+                    continue;
+                }
+
+                let guild = registry
+                    .user(session_id)
+                    .and_then(|user| state.store.guild(user, request.guild_id));
+                let Some(guild) = guild else {
+                    debug!(
+                        "Ignoring member request for unknown guild {}",
+                        request.guild_id
+                    );
+                    continue;
+                };
+
+                for chunk in
+                    members::chunks(&guild.members, &request, |user| state.presences.get(user))
+                {
+                    registry.dispatch(session_id, Event::MemberChunk(chunk));
+                }
+                debug!("Sent members of {}", request.guild_id);
+            }
+            RadianceEvent::PresenceUpdate(update) => {
+                let presence = Presence {
+                    status: update.status,
+                    since: u64::try_from(update.since).unwrap_or_default(),
+                    activities: update.activities,
+                    afk: update.afk,
+                };
+                registry.update_presence(session_id, presence);
+
+                if let Some(user) = registry.user(session_id) {
+                    refresh_presence(state, user);
+                }
+            }
+            RadianceEvent::VoiceStateUpdate(request) => {
+                let Some(guild_id) = request.guild_id else {
+                    debug!("Ignoring voice state update outside of a guild");
+                    continue;
+                };
+                let Some(user) = registry.user(session_id) else {
+                    continue;
+                };
+                let Some(guild) = state.store.guild(user, guild_id) else {
+                    debug!("Ignoring voice state update for unknown guild {guild_id}");
+                    continue;
+                };
+
+                match state
+                    .voice
+                    .update(guild, user, &session_id.to_string(), &request)
+                {
+                    Ok(change) => {
+                        // The replaced session has to be told it's out of
+                        // voice, everyone else only sees the new session
+                        if let Some(replaced) = change.replaced {
+                            if let Ok(replaced_id) = replaced.session_id.parse() {
+                                let update =
+                                    Event::VoiceStateUpdate(Box::new(VoiceStateUpdate(replaced)));
+                                registry.dispatch(replaced_id, update);
+                            }
+                        }
+                        publish_voice_state(registry, change.state);
+
+                        if change.connected {
+                            let server = state.voice_config.server(guild_id);
+                            registry.dispatch(session_id, Event::VoiceServerUpdate(server));
+                        }
+                    }
+                    Err(e) => debug!("Rejected voice state update: {e:?}"),
+                }
+            }
+            RadianceEvent::LazyRequest(request) => {
+                let guild = registry
+                    .user(session_id)
+                    .and_then(|user| state.store.guild(user, request.guild_id));
+                let (Some(guild), Some(channels)) = (guild, request.channels) else {
+                    continue;
+                };
+
+                // There's only one list per guild, so every channel's ranges
+                // are for the same list
+                let mut ranges = vec![];
+                for range in channels.into_values().flatten() {
+                    if !ranges.contains(&range) {
+                        ranges.push(range);
+                    }
+                }
+
+                let sync = state
+                    .member_lists
+                    .sync(request.guild_id, session_id, ranges, || {
+                        member_list(state, guild)
+                    });
+                registry.dispatch(session_id, sync);
+                debug!("Synced member list of {}", request.guild_id);
+            }
             _ => {
                 debug!("{:?}", event);
             }
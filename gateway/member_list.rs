@@ -0,0 +1,512 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::presence::UserPresence;
+use crate::sessions::SessionId;
+use radiance::events::models::{ListGroup, ListItem, ListMember, ListOp, ListRange, Status};
+use radiance::events::GuildMemberListUpdate;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use twilight_model::guild::{Member, Role};
+use twilight_model::id::marker::{GuildMarker, UserMarker};
+use twilight_model::id::Id;
+
+// Discord hashes the channel's permissions into the list's ID. Every member
+// can see every channel here, so there's only the one list
+const LIST_ID: &str = "everyone";
+
+/// A guild's member sidebar: online members grouped by their highest hoisted
+/// role, then everyone else online, then everyone offline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemberList {
+    groups: Vec<ListGroup>,
+    items: Vec<ListItem>,
+    member_count: u32,
+    online_count: u32,
+}
+
+impl MemberList {
+    pub fn new(
+        roles: &[Role],
+        members: &[Member],
+        presence: impl Fn(Id<UserMarker>) -> UserPresence,
+    ) -> Self {
+        let mut hoisted = roles.iter().filter(|role| role.hoist).collect::<Vec<_>>();
+        hoisted.sort_by_key(|role| Reverse((role.position, role.id)));
+
+        // One section per hoisted role, then online and offline
+        let mut sections = hoisted
+            .iter()
+            .map(|role| (role.id.to_string(), vec![]))
+            .chain([
+                ("online".to_string(), vec![]),
+                ("offline".to_string(), vec![]),
+            ])
+            .collect::<Vec<_>>();
+        let online = hoisted.len();
+        let offline = online + 1;
+
+        for member in members {
+            let presence = presence(member.user.id);
+            let section = if presence.status == Status::Offline {
+                offline
+            } else {
+                hoisted
+                    .iter()
+                    .position(|role| member.roles.contains(&role.id))
+                    .unwrap_or(online)
+            };
+
+            sections[section].1.push(Box::new(ListMember {
+                member: member.clone(),
+                presence: presence.member_presence(member.user.id),
+            }));
+        }
+
+        let mut groups = vec![];
+        let mut items = vec![];
+        for (id, mut members) in sections {
+            if members.is_empty() {
+                continue;
+            }
+            members
+                .sort_by_cached_key(|m| (display_name(&m.member).to_lowercase(), m.member.user.id));
+
+            let group = ListGroup {
+                id,
+                count: members.len() as u32,
+            };
+            groups.push(group.clone());
+            items.push(ListItem::Group(group));
+            items.extend(members.into_iter().map(ListItem::Member));
+        }
+
+        let offline_count = groups
+            .iter()
+            .find(|group| group.id == "offline")
+            .map_or(0, |group| group.count);
+
+        MemberList {
+            groups,
+            items,
+            member_count: members.len() as u32,
+            online_count: members.len() as u32 - offline_count,
+        }
+    }
+
+    /// The whole of each range, for clients that just subscribed.
+    pub fn sync(&self, guild_id: Id<GuildMarker>, ranges: &[ListRange]) -> GuildMemberListUpdate {
+        let ops = ranges
+            .iter()
+            .map(|range| ListOp::Sync {
+                range: *range,
+                items: self.range(range).to_vec(),
+            })
+            .collect();
+
+        self.payload(guild_id, ops)
+    }
+
+    /// What turns this list into `new`, if anything changed.
+    pub fn update(
+        &self,
+        guild_id: Id<GuildMarker>,
+        new: &MemberList,
+    ) -> Option<GuildMemberListUpdate> {
+        let ops = diff(&self.items, &new.items);
+
+        (!ops.is_empty()).then(|| new.payload(guild_id, ops))
+    }
+
+    // The items in `range`, which is inclusive and may go past the end
+    fn range(&self, range: &ListRange) -> &[ListItem] {
+        let start = (range.0 as usize).min(self.items.len());
+        let end = (range.1 as usize)
+            .saturating_add(1)
+            .clamp(start, self.items.len());
+
+        &self.items[start..end]
+    }
+
+    fn payload(&self, guild_id: Id<GuildMarker>, ops: Vec<ListOp>) -> GuildMemberListUpdate {
+        GuildMemberListUpdate {
+            guild_id,
+            id: LIST_ID.to_string(),
+            member_count: self.member_count,
+            online_count: self.online_count,
+            groups: self.groups.clone(),
+            ops,
+        }
+    }
+}
+
+fn display_name(member: &Member) -> &str {
+    member
+        .nick
+        .as_deref()
+        .or(member.user.global_name.as_deref())
+        .unwrap_or(&member.user.name)
+}
+
+#[derive(Eq, Hash, PartialEq)]
+enum ItemKey<'a> {
+    Group(&'a str),
+    Member(Id<UserMarker>),
+}
+
+fn key(item: &ListItem) -> ItemKey<'_> {
+    match item {
+        ListItem::Group(group) => ItemKey::Group(&group.id),
+        ListItem::Member(member) => ItemKey::Member(member.member.user.id),
+    }
+}
+
+// Ops that turn `old` into `new`, when applied in order. Items are matched by
+// group or user, so moves become a delete and an insert
+fn diff(old: &[ListItem], new: &[ListItem]) -> Vec<ListOp> {
+    let mut ops = vec![];
+    let mut current = old.to_vec();
+
+    // Removing from the back keeps the remaining indices valid
+    let keys = new.iter().map(key).collect::<HashSet<_>>();
+    for index in (0..current.len()).rev() {
+        if !keys.contains(&key(&current[index])) {
+            current.remove(index);
+            ops.push(ListOp::Delete {
+                index: index as u32,
+            });
+        }
+    }
+
+    for (index, item) in new.iter().enumerate() {
+        match current.get(index) {
+            Some(existing) if key(existing) == key(item) => {
+                if existing != item {
+                    current[index] = item.clone();
+                    ops.push(ListOp::Update {
+                        index: index as u32,
+                        item: item.clone(),
+                    });
+                }
+            }
+            _ => {
+                let moved = current[index..]
+                    .iter()
+                    .position(|existing| key(existing) == key(item));
+                if let Some(offset) = moved {
+                    current.remove(index + offset);
+                    ops.push(ListOp::Delete {
+                        index: (index + offset) as u32,
+                    });
+                }
+
+                current.insert(index, item.clone());
+                ops.push(ListOp::Insert {
+                    index: index as u32,
+                    item: item.clone(),
+                });
+            }
+        }
+    }
+
+    ops
+}
+
+struct Subscribed {
+    list: MemberList,
+    // The ranges each session last asked for
+    sessions: HashMap<SessionId, Vec<ListRange>>,
+}
+
+/// The latest member list of each guild someone subscribed to, so changes can
+/// be sent as ops.
+#[derive(Clone, Default)]
+pub struct MemberLists {
+    lists: Arc<Mutex<HashMap<Id<GuildMarker>, Subscribed>>>,
+}
+
+impl MemberLists {
+    /// Subscribes `session` to `ranges` of the guild's list, replacing the
+    /// ranges it asked for before, and syncs them. The list is built if nobody
+    /// subscribed before.
+    pub fn sync(
+        &self,
+        guild_id: Id<GuildMarker>,
+        session: SessionId,
+        ranges: Vec<ListRange>,
+        build: impl FnOnce() -> MemberList,
+    ) -> GuildMemberListUpdate {
+        let mut lists = self.lock();
+
+        let subscribed = lists.entry(guild_id).or_insert_with(|| Subscribed {
+            list: build(),
+            sessions: HashMap::new(),
+        });
+        let sync = subscribed.list.sync(guild_id, &ranges);
+        subscribed.sessions.insert(session, ranges);
+
+        sync
+    }
+
+    /// Replaces the guild's list, returning what changed for each session
+    /// that subscribed to a range that changed. Sessions that aren't `alive`
+    /// anymore are unsubscribed, and lists nobody subscribed to aren't kept.
+    pub fn refresh(
+        &self,
+        guild_id: Id<GuildMarker>,
+        list: MemberList,
+        alive: impl Fn(SessionId) -> bool,
+    ) -> Vec<(SessionId, GuildMemberListUpdate)> {
+        let mut lists = self.lock();
+        lists.retain(|_, subscribed| {
+            subscribed.sessions.retain(|&session, _| alive(session));
+            !subscribed.sessions.is_empty()
+        });
+
+        let Some(current) = lists.get_mut(&guild_id) else {
+            return vec![];
+        };
+        let old = std::mem::replace(&mut current.list, list);
+        let Some(update) = old.update(guild_id, &current.list) else {
+            return vec![];
+        };
+
+        current
+            .sessions
+            .iter()
+            .filter(|(_, ranges)| {
+                ranges
+                    .iter()
+                    .any(|range| old.range(range) != current.list.range(range))
+            })
+            .map(|(&session, _)| (session, update.clone()))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Id<GuildMarker>, Subscribed>> {
+        // Lists are replaced as a whole, so a poisoned lock is fine
+        self.lists.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::guild::{MemberFlags, Permissions, RoleFlags};
+    use twilight_model::id::marker::RoleMarker;
+    use twilight_model::user::User;
+    use twilight_model::util::Timestamp;
+
+    fn role(id: u64, position: i64) -> Role {
+        Role {
+            color: 0,
+            hoist: true,
+            icon: None,
+            id: Id::new(id),
+            managed: false,
+            mentionable: false,
+            name: format!("role {id}"),
+            permissions: Permissions::empty(),
+            position,
+            flags: RoleFlags::empty(),
+            tags: None,
+            unicode_emoji: None,
+        }
+    }
+
+    fn member(id: u64, name: &str, roles: &[u64]) -> Member {
+        Member {
+            avatar: None,
+            communication_disabled_until: None,
+            deaf: false,
+            flags: MemberFlags::empty(),
+            joined_at: Timestamp::from_secs(1700000000).unwrap(),
+            mute: false,
+            nick: None,
+            pending: false,
+            premium_since: None,
+            roles: roles.iter().map(|&id| Id::<RoleMarker>::new(id)).collect(),
+            user: User {
+                accent_color: None,
+                avatar: None,
+                avatar_decoration: None,
+                banner: None,
+                bot: false,
+                discriminator: 0,
+                email: None,
+                flags: None,
+                global_name: None,
+                id: Id::new(id),
+                locale: None,
+                mfa_enabled: None,
+                name: name.to_string(),
+                premium_type: None,
+                public_flags: None,
+                system: None,
+                verified: None,
+            },
+        }
+    }
+
+    fn list(members: &[Member], online: &[u64]) -> MemberList {
+        let roles = [role(10, 1), role(20, 2)];
+
+        MemberList::new(&roles, members, |user| UserPresence {
+            status: if online.contains(&user.get()) {
+                Status::Online
+            } else {
+                Status::Offline
+            },
+            activities: vec![],
+        })
+    }
+
+    // How the list looks to a client, e.g. ["20", "2", "online", "1"]
+    fn names(items: &[ListItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                ListItem::Group(group) => group.id.clone(),
+                ListItem::Member(member) => member.member.user.id.to_string(),
+            })
+            .collect()
+    }
+
+    fn apply(items: &mut Vec<ListItem>, ops: Vec<ListOp>) {
+        for op in ops {
+            match op {
+                ListOp::Insert { index, item } => items.insert(index as usize, item),
+                ListOp::Update { index, item } => items[index as usize] = item,
+                ListOp::Delete { index } => {
+                    items.remove(index as usize);
+                }
+                ListOp::Sync { .. } => panic!("Unexpected sync"),
+            }
+        }
+    }
+
+    fn members() -> Vec<Member> {
+        vec![
+            member(1, "bee", &[]),
+            member(2, "ant", &[10, 20]),
+            member(3, "cat", &[10]),
+            member(4, "Aardvark", &[]),
+        ]
+    }
+
+    #[test]
+    fn groups_by_hoisted_role_and_status() {
+        let list = list(&members(), &[1, 2, 3]);
+
+        assert_eq!(
+            names(&list.items),
+            ["20", "2", "10", "3", "online", "1", "offline", "4"]
+        );
+        assert_eq!((list.member_count, list.online_count), (4, 3));
+        assert_eq!(list.groups.len(), 4);
+    }
+
+    #[test]
+    fn syncs_ranges() {
+        let list = list(&members(), &[1, 2, 3]);
+
+        let update = list.sync(Id::new(1), &[ListRange(0, 2), ListRange(6, 99)]);
+        let [ListOp::Sync { items: first, .. }, ListOp::Sync { items: second, .. }] =
+            update.ops.as_slice()
+        else {
+            panic!("Expected two syncs, got {:?}", update.ops);
+        };
+        assert_eq!(names(first), ["20", "2", "10"]);
+        assert_eq!(names(second), ["offline", "4"]);
+    }
+
+    #[test]
+    fn updates_apply_to_the_old_list() {
+        let members = members();
+        let states: [&[u64]; 5] = [&[1, 2, 3], &[], &[4], &[1, 2, 3, 4], &[2]];
+
+        for old in states {
+            for new in states {
+                let old = list(&members, old);
+                let new = list(&members, new);
+
+                let mut items = old.items.clone();
+                match old.update(Id::new(1), &new) {
+                    Some(update) => apply(&mut items, update.ops),
+                    None => assert_eq!(old, new),
+                }
+                assert_eq!(names(&items), names(&new.items));
+                assert_eq!(items, new.items);
+            }
+        }
+    }
+
+    #[test]
+    fn only_keeps_subscribed_lists() {
+        let lists = MemberLists::default();
+        let members = members();
+        let alive = |_| true;
+
+        assert!(lists
+            .refresh(Id::new(1), list(&members, &[]), alive)
+            .is_empty());
+
+        lists.sync(Id::new(1), Id::new(5), vec![ListRange(0, 99)], || {
+            list(&members, &[])
+        });
+        let updates = lists.refresh(Id::new(1), list(&members, &[1]), alive);
+        let [(session, update)] = updates.as_slice() else {
+            panic!("Expected one update, got {updates:?}");
+        };
+        assert_eq!(*session, Id::new(5));
+        assert_eq!(update.online_count, 1);
+        assert!(lists
+            .refresh(Id::new(1), list(&members, &[1]), alive)
+            .is_empty());
+
+        // Once the session is gone, so is the list
+        assert!(lists
+            .refresh(Id::new(1), list(&members, &[]), |_| false)
+            .is_empty());
+        assert!(lists.lock().is_empty());
+    }
+
+    #[test]
+    fn only_updates_sessions_subscribed_to_changes() {
+        let lists = MemberLists::default();
+        let members = members();
+        let everyone = Id::new(5);
+        let top = Id::new(6);
+        // Subscribed to another guild, but never sent an op 14 for this one
+        let elsewhere = Id::new(7);
+
+        lists.sync(Id::new(1), everyone, vec![ListRange(0, 99)], || {
+            list(&members, &[1, 2, 3])
+        });
+        lists.sync(Id::new(1), top, vec![ListRange(0, 1)], || unreachable!());
+        lists.sync(Id::new(2), elsewhere, vec![ListRange(0, 99)], || {
+            list(&members, &[])
+        });
+
+        // Only the online section changes, which is past the top two items
+        let updates = lists.refresh(Id::new(1), list(&members, &[1, 2]), |_| true);
+        let sessions = updates
+            .iter()
+            .map(|(session, _)| *session)
+            .collect::<Vec<_>>();
+        assert_eq!(sessions, [everyone]);
+
+        // Empties the top role
+        let updates = lists.refresh(Id::new(1), list(&members, &[1]), |_| true);
+        let mut sessions = updates
+            .iter()
+            .map(|(session, _)| *session)
+            .collect::<Vec<_>>();
+        sessions.sort();
+        assert_eq!(sessions, [everyone, top]);
+    }
+}
@@ -0,0 +1,271 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::presence::UserPresence;
+use radiance::events::models::Status;
+use radiance::events::RequestGuildMembers;
+use twilight_model::gateway::payload::incoming::MemberChunk;
+use twilight_model::guild::Member;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+// Discord's limits for member requests
+const CHUNK_SIZE: usize = 1000;
+const QUERY_LIMIT: usize = 100;
+const USER_IDS_LIMIT: usize = 100;
+const NONCE_LIMIT: usize = 32;
+
+/// Answers a member request from `members`, the guild's members, in as many
+/// chunks as it takes. There's always at least one chunk, so clients waiting
+/// for the nonce hear back.
+pub fn chunks(
+    members: &[Member],
+    request: &RequestGuildMembers,
+    presence: impl Fn(Id<UserMarker>) -> UserPresence,
+) -> Vec<MemberChunk> {
+    let (found, not_found) = match &request.user_ids {
+        Some(user_ids) => find(members, user_ids),
+        None => (search(members, request), vec![]),
+    };
+
+    // Longer nonces are ignored, like Discord does
+    let nonce = request
+        .nonce
+        .clone()
+        .filter(|nonce| nonce.len() <= NONCE_LIMIT);
+
+    let mut chunks = found
+        .chunks(CHUNK_SIZE)
+        .map(<[_]>::to_vec)
+        .collect::<Vec<_>>();
+    if chunks.is_empty() {
+        chunks.push(vec![]);
+    }
+    let chunk_count = chunks.len() as u32;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, members)| {
+            // Offline members don't have one
+            let presences = if request.presences {
+                members
+                    .iter()
+                    .filter_map(|member| {
+                        let user = member.user.id;
+                        let presence = presence(user);
+                        (presence.status != Status::Offline)
+                            .then(|| presence.guild_presence(request.guild_id, user))
+                    })
+                    .collect()
+            } else {
+                vec![]
+            };
+
+            MemberChunk {
+                chunk_count,
+                chunk_index: index as u32,
+                guild_id: request.guild_id,
+                members,
+                nonce: nonce.clone(),
+                // Only the first chunk says who's missing
+                not_found: if index == 0 {
+                    not_found.clone()
+                } else {
+                    vec![]
+                },
+                presences,
+            }
+        })
+        .collect()
+}
+
+fn find(members: &[Member], user_ids: &[Id<UserMarker>]) -> (Vec<Member>, Vec<Id<UserMarker>>) {
+    let mut found = vec![];
+    let mut not_found = vec![];
+
+    for user_id in user_ids.iter().take(USER_IDS_LIMIT) {
+        match members.iter().find(|member| member.user.id == *user_id) {
+            Some(member) => found.push(member.clone()),
+            None => not_found.push(*user_id),
+        }
+    }
+
+    (found, not_found)
+}
+
+// Members whose username, display name or nickname start with the query. An
+// empty query with a limit of 0 is everyone
+fn search(members: &[Member], request: &RequestGuildMembers) -> Vec<Member> {
+    let query = request.query.as_deref().unwrap_or_default().to_lowercase();
+    let limit = match request.limit as usize {
+        0 if query.is_empty() => usize::MAX,
+        0 => QUERY_LIMIT,
+        limit => limit.min(QUERY_LIMIT),
+    };
+
+    members
+        .iter()
+        .filter(|member| {
+            let user = &member.user;
+            let names = [
+                Some(&user.name),
+                user.global_name.as_ref(),
+                member.nick.as_ref(),
+            ];
+
+            names
+                .into_iter()
+                .flatten()
+                .any(|name| name.to_lowercase().starts_with(&query))
+        })
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::gateway::presence;
+    use twilight_model::guild::MemberFlags;
+    use twilight_model::user::User;
+    use twilight_model::util::Timestamp;
+
+    fn member(id: u64, name: &str) -> Member {
+        Member {
+            avatar: None,
+            communication_disabled_until: None,
+            deaf: false,
+            flags: MemberFlags::empty(),
+            joined_at: Timestamp::from_secs(1700000000).unwrap(),
+            mute: false,
+            nick: None,
+            pending: false,
+            premium_since: None,
+            roles: vec![],
+            user: User {
+                accent_color: None,
+                avatar: None,
+                avatar_decoration: None,
+                banner: None,
+                bot: false,
+                discriminator: 0,
+                email: None,
+                flags: None,
+                global_name: None,
+                id: Id::new(id),
+                locale: None,
+                mfa_enabled: None,
+                name: name.to_string(),
+                premium_type: None,
+                public_flags: None,
+                system: None,
+                verified: None,
+            },
+        }
+    }
+
+    fn request() -> RequestGuildMembers {
+        RequestGuildMembers {
+            guild_id: Id::new(1),
+            query: Some(String::new()),
+            limit: 0,
+            presences: false,
+            user_ids: None,
+            nonce: Some("nonce".to_string()),
+        }
+    }
+
+    fn offline(_: Id<UserMarker>) -> UserPresence {
+        UserPresence::offline()
+    }
+
+    fn ids(chunk: &MemberChunk) -> Vec<u64> {
+        chunk.members.iter().map(|m| m.user.id.get()).collect()
+    }
+
+    #[test]
+    fn searches_by_name() {
+        let mut members = vec![member(1, "Person"), member(2, "friend"), member(3, "other")];
+        members[2].nick = Some("Friendly".to_string());
+
+        let mut request = request();
+        request.query = Some("fRi".to_string());
+        let chunks = chunks(&members, &request, offline);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(ids(&chunks[0]), [2, 3]);
+        assert_eq!(chunks[0].nonce.as_deref(), Some("nonce"));
+
+        request.limit = 1;
+        assert_eq!(ids(&super::chunks(&members, &request, offline)[0]), [2]);
+
+        request.query = Some("nobody".to_string());
+        let chunks = super::chunks(&members, &request, offline);
+        assert_eq!((chunks.len(), chunks[0].members.len()), (1, 0));
+    }
+
+    #[test]
+    fn finds_users() {
+        let members = [member(1, "person"), member(2, "friend")];
+
+        let mut request = request();
+        request.user_ids = Some(vec![Id::new(2), Id::new(3)]);
+        let chunks = chunks(&members, &request, offline);
+        assert_eq!(ids(&chunks[0]), [2]);
+        assert_eq!(chunks[0].not_found, [Id::new(3)]);
+    }
+
+    #[test]
+    fn sends_presences_if_requested() {
+        let members = [member(1, "person"), member(2, "friend")];
+        let presence = |user: Id<UserMarker>| {
+            if user == Id::new(1) {
+                UserPresence {
+                    status: Status::Online,
+                    activities: vec![],
+                }
+            } else {
+                UserPresence::offline()
+            }
+        };
+
+        let mut request = request();
+        assert!(chunks(&members, &request, presence)[0].presences.is_empty());
+
+        request.presences = true;
+        let presences = &chunks(&members, &request, presence)[0].presences;
+        assert_eq!(presences.len(), 1);
+        assert_eq!(presences[0].user.id(), Id::new(1));
+        assert_eq!(presences[0].guild_id, request.guild_id);
+        assert_eq!(presences[0].status, presence::Status::Online);
+    }
+
+    #[test]
+    fn splits_into_chunks() {
+        let members = (1..=2500)
+            .map(|id| member(id, "member"))
+            .collect::<Vec<_>>();
+
+        let mut request = request();
+        request.nonce = Some("n".repeat(NONCE_LIMIT + 1));
+        let chunks = chunks(&members, &request, offline);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].members.len(), 500);
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!((chunk.chunk_index, chunk.chunk_count), (index as u32, 3));
+            assert_eq!(chunk.nonce, None);
+        }
+
+        // Searching by name is capped
+        request.query = Some("m".to_string());
+        request.limit = 500;
+        assert_eq!(
+            super::chunks(&members, &request, offline)[0].members.len(),
+            QUERY_LIMIT
+        );
+    }
+}
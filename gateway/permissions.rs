@@ -0,0 +1,112 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use radiance::events::models::Guild;
+use twilight_model::channel::permission_overwrite::PermissionOverwriteType;
+use twilight_model::channel::Channel;
+use twilight_model::guild::{Member, Permissions};
+
+/// What `member` may do in `channel`, following Discord's order: the guild's
+/// roles, then the channel's overwrites for everyone, roles and the member.
+pub fn channel_permissions(guild: &Guild, member: &Member, channel: &Channel) -> Permissions {
+    if guild.properties.owner_id == member.user.id {
+        return Permissions::all();
+    }
+
+    // The @everyone role has the guild's ID
+    let mut permissions = guild
+        .roles
+        .iter()
+        .filter(|role| role.id == guild.id.cast() || member.roles.contains(&role.id))
+        .fold(Permissions::empty(), |permissions, role| {
+            permissions | role.permissions
+        });
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Permissions::all();
+    }
+
+    let overwrites = channel.permission_overwrites.as_deref().unwrap_or_default();
+
+    if let Some(everyone) = overwrites.iter().find(|o| o.id == guild.id.cast()) {
+        permissions = (permissions - everyone.deny) | everyone.allow;
+    }
+
+    let (deny, allow) = overwrites
+        .iter()
+        .filter(|o| o.kind == PermissionOverwriteType::Role && member.roles.contains(&o.id.cast()))
+        .fold(
+            (Permissions::empty(), Permissions::empty()),
+            |(deny, allow), o| (deny | o.deny, allow | o.allow),
+        );
+    permissions = (permissions - deny) | allow;
+
+    let own = overwrites
+        .iter()
+        .find(|o| o.kind == PermissionOverwriteType::Member && o.id == member.user.id.cast());
+    if let Some(own) = own {
+        permissions = (permissions - own.deny) | own.allow;
+    }
+
+    permissions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radiance::store::{AccountStore, StoredGuild};
+    use twilight_model::channel::permission_overwrite::PermissionOverwrite;
+    use twilight_model::id::Id;
+
+    fn guild() -> StoredGuild {
+        let store = AccountStore::from_json(include_str!("../store.example.json")).unwrap();
+
+        store
+            .guild(Id::new(967478107550388235), Id::new(1201246479224631326))
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn applies_overwrites_in_order() {
+        let mut guild = guild();
+        let owner = guild.members[0].clone();
+        let member = guild.members[1].clone();
+        assert_eq!(guild.guild.properties.owner_id, owner.user.id);
+
+        let overwrite = |id: u64, kind, allow, deny| PermissionOverwrite {
+            allow,
+            deny,
+            id: Id::new(id),
+            kind,
+        };
+        let channel = &mut guild.guild.channels[1];
+        channel.permission_overwrites = Some(vec![
+            overwrite(
+                guild.guild.id.get(),
+                PermissionOverwriteType::Role,
+                Permissions::empty(),
+                Permissions::CONNECT,
+            ),
+            overwrite(
+                member.user.id.get(),
+                PermissionOverwriteType::Member,
+                Permissions::SPEAK,
+                Permissions::empty(),
+            ),
+        ]);
+        let channel = &guild.guild.channels[1];
+
+        let permissions = channel_permissions(&guild.guild, &member, channel);
+        assert!(!permissions.contains(Permissions::CONNECT));
+        assert!(permissions.contains(Permissions::VIEW_CHANNEL | Permissions::SPEAK));
+
+        // Owners can do anything, whatever the overwrites say
+        assert_eq!(
+            channel_permissions(&guild.guild, &owner, channel),
+            Permissions::all()
+        );
+    }
+}
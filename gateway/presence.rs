@@ -0,0 +1,212 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use radiance::events::models::{ClientStatus, MemberPresence, Presence, PresenceUser, Status};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use twilight_model::gateway::presence::{self, Activity, UserOrId};
+use twilight_model::id::marker::{GuildMarker, UserMarker};
+use twilight_model::id::Id;
+
+/// What everyone else sees of a user, across all of their sessions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UserPresence {
+    pub status: Status,
+    pub activities: Vec<Activity>,
+}
+
+impl UserPresence {
+    pub fn offline() -> Self {
+        UserPresence {
+            status: Status::Offline,
+            activities: vec![],
+        }
+    }
+
+    /// Combines the presences of a user's sessions. Sessions that are afk
+    /// only count if all of them are, and the most present status wins.
+    pub fn aggregate(presences: &[Presence]) -> Self {
+        let active = presences.iter().filter(|p| !p.afk).collect::<Vec<_>>();
+        let presences = if active.is_empty() {
+            presences.iter().collect()
+        } else {
+            active
+        };
+
+        let Some(status) = presences.iter().map(|p| p.status).min_by_key(|&s| rank(s)) else {
+            return Self::offline();
+        };
+        // Invisible users look offline, activities and all
+        if matches!(
+            status,
+            Status::Invisible | Status::Offline | Status::Unknown
+        ) {
+            return Self::offline();
+        }
+
+        let mut activities = vec![];
+        for activity in presences.iter().flat_map(|p| &p.activities) {
+            if !activities.contains(activity) {
+                activities.push(activity.clone());
+            }
+        }
+
+        UserPresence { status, activities }
+    }
+
+    pub fn member_presence(&self, user: Id<UserMarker>) -> MemberPresence {
+        MemberPresence {
+            user: PresenceUser { id: user },
+            status: self.status,
+            // Only the web client exists so far
+            client_status: ClientStatus {
+                web: (self.status != Status::Offline).then_some(self.status),
+                ..Default::default()
+            },
+            activities: self.activities.clone(),
+        }
+    }
+
+    /// The presence as sent in member chunks.
+    pub fn guild_presence(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user: Id<UserMarker>,
+    ) -> presence::Presence {
+        presence::Presence {
+            activities: self.activities.clone(),
+            client_status: presence::ClientStatus {
+                web: (self.status != Status::Offline).then(|| self.status.into()),
+                desktop: None,
+                mobile: None,
+            },
+            guild_id,
+            status: self.status.into(),
+            user: UserOrId::UserId { id: user },
+        }
+    }
+}
+
+// Lower is more present
+fn rank(status: Status) -> u8 {
+    match status {
+        Status::DoNotDisturb => 0,
+        Status::Online => 1,
+        Status::Idle => 2,
+        Status::Invisible => 3,
+        Status::Offline | Status::Unknown => 4,
+    }
+}
+
+/// The presence each user was last seen with, so only changes are sent.
+#[derive(Clone, Default)]
+pub struct PresenceService {
+    presences: Arc<Mutex<HashMap<Id<UserMarker>, UserPresence>>>,
+}
+
+impl PresenceService {
+    pub fn get(&self, user: Id<UserMarker>) -> UserPresence {
+        self.lock()
+            .get(&user)
+            .cloned()
+            .unwrap_or_else(UserPresence::offline)
+    }
+
+    /// Records the presences of `user`'s connected sessions, returning the
+    /// new presence if others need to hear about it.
+    pub fn update(&self, user: Id<UserMarker>, presences: &[Presence]) -> Option<UserPresence> {
+        let presence = UserPresence::aggregate(presences);
+        let mut known = self.lock();
+
+        if known.get(&user).unwrap_or(&UserPresence::offline()) == &presence {
+            return None;
+        }
+
+        if presence.status == Status::Offline {
+            known.remove(&user);
+        } else {
+            known.insert(user, presence.clone());
+        }
+
+        Some(presence)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Id<UserMarker>, UserPresence>> {
+        // Presences are replaced whole, so a poisoned lock is fine
+        self.presences.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::gateway::presence::{ActivityType, MinimalActivity};
+
+    fn presence(status: Status, afk: bool, activity: Option<&str>) -> Presence {
+        Presence {
+            status,
+            since: 0,
+            activities: activity
+                .map(|name| {
+                    Activity::from(MinimalActivity {
+                        kind: ActivityType::Playing,
+                        name: name.to_string(),
+                        url: None,
+                    })
+                })
+                .into_iter()
+                .collect(),
+            afk,
+        }
+    }
+
+    #[test]
+    fn aggregates_sessions() {
+        let online = presence(Status::Online, false, Some("game"));
+        let dnd = presence(Status::DoNotDisturb, false, Some("music"));
+        let afk = presence(Status::Idle, true, Some("idling"));
+
+        let both = UserPresence::aggregate(&[online.clone(), dnd, afk.clone()]);
+        assert_eq!(both.status, Status::DoNotDisturb);
+        assert_eq!(both.activities.len(), 2);
+
+        assert_eq!(
+            UserPresence::aggregate(&[online.clone(), afk.clone()]).status,
+            Status::Online
+        );
+        assert_eq!(UserPresence::aggregate(&[afk]).status, Status::Idle);
+        assert_eq!(UserPresence::aggregate(&[]), UserPresence::offline());
+
+        let invisible = presence(Status::Invisible, false, Some("hidden"));
+        assert_eq!(
+            UserPresence::aggregate(std::slice::from_ref(&invisible)),
+            UserPresence::offline()
+        );
+        assert_eq!(
+            UserPresence::aggregate(&[invisible, online]).status,
+            Status::Online
+        );
+    }
+
+    #[test]
+    fn only_reports_changes() {
+        let service = PresenceService::default();
+        let user = Id::new(1);
+        let online = [presence(Status::Online, false, None)];
+
+        assert_eq!(service.get(user), UserPresence::offline());
+        assert_eq!(
+            service.update(user, &online).unwrap().status,
+            Status::Online
+        );
+        assert_eq!(service.update(user, &online), None);
+        assert_eq!(service.get(user).status, Status::Online);
+
+        // The last session disconnected
+        assert_eq!(service.update(user, &[]), Some(UserPresence::offline()));
+        assert_eq!(service.update(user, &[]), None);
+    }
+}
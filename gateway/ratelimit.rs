@@ -0,0 +1,69 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::time::{Duration, Instant};
+
+/// Allows `limit` events per fixed `window`.
+pub struct RateLimit {
+    limit: u32,
+    window: Duration,
+    start: Instant,
+    count: u32,
+}
+
+impl RateLimit {
+    /// Discord's limit for everything a client sends
+    pub fn gateway(now: Instant) -> Self {
+        Self::new(120, Duration::from_secs(60), now)
+    }
+
+    /// Member requests are answered with up to a whole guild, so they're
+    /// limited on their own
+    pub fn member_requests(now: Instant) -> Self {
+        Self::new(10, Duration::from_secs(10), now)
+    }
+
+    pub fn new(limit: u32, window: Duration, now: Instant) -> Self {
+        RateLimit {
+            limit,
+            window,
+            start: now,
+            count: 0,
+        }
+    }
+
+    /// Counts an event, returning false if it's over the limit.
+    pub fn check(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.start) >= self.window {
+            self.start = now;
+            self.count = 0;
+        }
+
+        self.count += 1;
+        self.count <= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimit;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn limits_per_window() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(2, Duration::from_secs(10), start);
+
+        assert!(limit.check(start));
+        assert!(limit.check(start + Duration::from_secs(1)));
+        assert!(!limit.check(start + Duration::from_secs(2)));
+
+        let next = start + Duration::from_secs(10);
+        assert!(limit.check(next));
+        assert!(limit.check(next));
+        assert!(!limit.check(next));
+    }
+}
@@ -0,0 +1,566 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::connection::EventSender;
+use radiance::bus::Topic;
+use radiance::events::models::{ClientInfo, Presence, Session};
+use radiance::events::{EventPayload, IntoPayload, Resume};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use twilight_model::gateway::event::Event;
+use twilight_model::id::marker::{GenericMarker, UserMarker};
+use twilight_model::id::Id;
+
+// How many dispatches are kept around for a resume
+const REPLAY_CAPACITY: usize = 1024;
+// How long a disconnected session can still be resumed
+const RESUME_TIMEOUT: Duration = Duration::from_secs(180);
+
+pub type SessionId = Id<GenericMarker>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ResumeError {
+    UnknownSession,
+    InvalidToken,
+    InvalidSequence,
+    // The client missed more events than are still buffered
+    WindowExceeded,
+}
+
+struct SessionState {
+    token: String,
+    user: Id<UserMarker>,
+    client_info: ClientInfo,
+    presence: Presence,
+    sequence: u32,
+    replay: VecDeque<EventPayload>,
+    // The connection the session is attached to, if any
+    sender: Option<EventSender>,
+    disconnected_at: Option<Instant>,
+}
+
+impl SessionState {
+    fn new(
+        token: &str,
+        user: Id<UserMarker>,
+        client_info: ClientInfo,
+        presence: Presence,
+        sender: EventSender,
+    ) -> Self {
+        SessionState {
+            token: token.to_string(),
+            user,
+            client_info,
+            presence,
+            sequence: 0,
+            replay: VecDeque::new(),
+            sender: Some(sender),
+            disconnected_at: None,
+        }
+    }
+
+    fn dispatch(&mut self, event: impl IntoPayload, replay_capacity: usize) {
+        let payload = event.into_payload(&mut self.sequence);
+
+        // Payloads without a sequence aren't dispatches and can't be replayed
+        if payload.sequence().is_some() && replay_capacity > 0 {
+            if self.replay.len() == replay_capacity {
+                self.replay.pop_front();
+            }
+            self.replay.push_back(payload.clone());
+        }
+
+        self.send(payload);
+    }
+
+    fn send(&mut self, payload: EventPayload) {
+        if let Some(sender) = &self.sender {
+            // The connection is closing, either because it's gone already or
+            // because it's too slow. Either way, it can resume later
+            if sender.send(payload).is_err() {
+                self.detach();
+            }
+        }
+    }
+
+    fn attach(&mut self, sender: EventSender) {
+        // Only one connection can be attached, so the old one has to close
+        if let Some(old) = self.sender.replace(sender) {
+            old.replace();
+        }
+        self.disconnected_at = None;
+    }
+
+    fn detach(&mut self) {
+        self.sender = None;
+        self.disconnected_at = Some(Instant::now());
+    }
+}
+
+struct Sessions {
+    next_id: u64,
+    sessions: HashMap<SessionId, SessionState>,
+    subscribers: HashMap<Topic, HashSet<SessionId>>,
+}
+
+/// Keeps track of every session, so clients can resume them after
+/// reconnecting, and so events can be dispatched to them from anywhere.
+/// Cloning it is cheap and shares the same sessions.
+#[derive(Clone)]
+pub struct SessionRegistry {
+    inner: Arc<Mutex<Sessions>>,
+    replay_capacity: usize,
+    resume_timeout: Duration,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::with_limits(REPLAY_CAPACITY, RESUME_TIMEOUT)
+    }
+
+    pub fn with_limits(replay_capacity: usize, resume_timeout: Duration) -> Self {
+        SessionRegistry {
+            inner: Arc::new(Mutex::new(Sessions {
+                next_id: 1,
+                sessions: HashMap::new(),
+                subscribers: HashMap::new(),
+            })),
+            replay_capacity,
+            resume_timeout,
+        }
+    }
+
+    /// Creates a new session for a connection that identified as `user`.
+    pub fn register(
+        &self,
+        token: &str,
+        user: Id<UserMarker>,
+        client_info: ClientInfo,
+        presence: Presence,
+        sender: EventSender,
+    ) -> SessionId {
+        let mut inner = self.lock();
+        self.prune(&mut inner);
+
+        let id = Id::new(inner.next_id);
+        inner.next_id += 1;
+
+        inner.sessions.insert(
+            id,
+            SessionState::new(token, user, client_info, presence, sender),
+        );
+
+        id
+    }
+
+    /// Sends a dispatch to `session` and keeps it around to be replayed.
+    /// Sessions without a connection only keep it for when they resume.
+    pub fn dispatch(&self, session: SessionId, event: impl IntoPayload) {
+        if let Some(state) = self.lock().sessions.get_mut(&session) {
+            state.dispatch(event, self.replay_capacity);
+        }
+    }
+
+    /// Makes `session` receive everything published to `topic`, until it
+    /// can't be resumed anymore.
+    pub fn subscribe(&self, session: SessionId, topic: Topic) {
+        let mut inner = self.lock();
+
+        if inner.sessions.contains_key(&session) {
+            inner.subscribers.entry(topic).or_default().insert(session);
+        }
+    }
+
+    /// Dispatches `event` to every session subscribed to `topic`.
+    pub fn publish<E: IntoPayload + Clone>(&self, topic: Topic, event: &E) {
+        let mut inner = self.lock();
+        // Otherwise sessions nobody resumes would keep buffering forever
+        self.prune(&mut inner);
+        let Sessions {
+            sessions,
+            subscribers,
+            ..
+        } = &mut *inner;
+
+        for session in subscribers.get(&topic).into_iter().flatten() {
+            if let Some(state) = sessions.get_mut(session) {
+                state.dispatch(event.clone(), self.replay_capacity);
+            }
+        }
+    }
+
+    /// The sessions `user` currently has a connection for.
+    pub fn sessions(&self, user: Id<UserMarker>) -> Vec<Session> {
+        self.lock()
+            .sessions
+            .iter()
+            .filter(|(_, state)| state.user == user && state.sender.is_some())
+            .map(|(id, state)| Session {
+                activities: state.presence.activities.clone(),
+                client_info: state.client_info.clone(),
+                session_id: *id,
+                status: state.presence.status,
+            })
+            .collect()
+    }
+
+    /// The presences of `user`'s connected sessions.
+    pub fn presences(&self, user: Id<UserMarker>) -> Vec<Presence> {
+        self.lock()
+            .sessions
+            .values()
+            .filter(|state| state.user == user && state.sender.is_some())
+            .map(|state| state.presence.clone())
+            .collect()
+    }
+
+    pub fn update_presence(&self, session: SessionId, presence: Presence) {
+        if let Some(state) = self.lock().sessions.get_mut(&session) {
+            state.presence = presence;
+        }
+    }
+
+    /// The user `session` belongs to.
+    pub fn user(&self, session: SessionId) -> Option<Id<UserMarker>> {
+        self.lock().sessions.get(&session).map(|state| state.user)
+    }
+
+    /// The sequence of the last dispatch sent to `session`.
+    pub fn sequence(&self, session: SessionId) -> Option<u32> {
+        self.lock()
+            .sessions
+            .get(&session)
+            .map(|state| state.sequence)
+    }
+
+    /// Detaches the connection behind `sender` from `session`, starting its
+    /// resume timeout. Does nothing if another connection resumed it since,
+    /// which is when it returns false.
+    pub fn disconnect(&self, session: SessionId, sender: &EventSender) -> bool {
+        let mut inner = self.lock();
+        self.prune(&mut inner);
+
+        let Some(state) = inner.sessions.get_mut(&session) else {
+            return false;
+        };
+        let attached = state
+            .sender
+            .as_ref()
+            .is_some_and(|s| s.same_connection(sender));
+        if attached {
+            state.detach();
+        }
+
+        attached
+    }
+
+    /// Attaches a new connection to a previous session, sending it the
+    /// dispatches it missed followed by `RESUMED`.
+    pub fn resume(&self, resume: &Resume, sender: EventSender) -> Result<(), ResumeError> {
+        let mut inner = self.lock();
+        self.prune(&mut inner);
+
+        let state = inner
+            .sessions
+            .get_mut(&resume.session_id)
+            .ok_or(ResumeError::UnknownSession)?;
+
+        if state.token != resume.token {
+            return Err(ResumeError::InvalidToken);
+        }
+        if resume.seq > state.sequence {
+            return Err(ResumeError::InvalidSequence);
+        }
+
+        // Everything after the client's sequence has to still be buffered
+        let missed = (state.sequence - resume.seq) as usize;
+        if missed > state.replay.len() {
+            return Err(ResumeError::WindowExceeded);
+        }
+
+        // Replayed dispatches keep their original sequence. This happens under
+        // the lock, so nothing else can be dispatched in between
+        let replay = state
+            .replay
+            .range(state.replay.len() - missed..)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        state.attach(sender);
+        for payload in replay {
+            state.send(payload);
+        }
+        state.dispatch(Event::Resumed, self.replay_capacity);
+
+        Ok(())
+    }
+
+    // Forgets sessions that can't be resumed anymore
+    fn prune(&self, inner: &mut Sessions) {
+        inner.sessions.retain(|_, state| {
+            state
+                .disconnected_at
+                .is_none_or(|at| at.elapsed() < self.resume_timeout)
+        });
+
+        let sessions = &inner.sessions;
+        inner.subscribers.retain(|_, subscribers| {
+            subscribers.retain(|session| sessions.contains_key(session));
+            !subscribers.is_empty()
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Sessions> {
+        // Sessions are never left half updated, so a poisoned lock is fine
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radiance::events::models::Status;
+    use tokio::sync::mpsc::Receiver;
+
+    fn connection() -> (EventSender, Receiver<EventPayload>) {
+        EventSender::new(16)
+    }
+
+    fn register(registry: &SessionRegistry, sender: EventSender) -> SessionId {
+        let client_info = ClientInfo {
+            client: "web".to_string(),
+            os: "Linux".to_string(),
+            version: 0,
+        };
+
+        let presence = Presence {
+            status: Status::Online,
+            since: 0,
+            activities: vec![],
+            afk: false,
+        };
+
+        registry.register("token", Id::new(1), client_info, presence, sender)
+    }
+
+    fn resume(session: SessionId, token: &str, seq: u32) -> Resume {
+        Resume {
+            token: token.to_string(),
+            session_id: session,
+            seq,
+        }
+    }
+
+    fn received(events: &mut Receiver<EventPayload>) -> Vec<Option<u32>> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .map(|payload| payload.sequence())
+            .collect()
+    }
+
+    #[test]
+    fn issues_unique_ids() {
+        let registry = SessionRegistry::new();
+
+        let first = register(&registry, connection().0);
+        let second = register(&registry.clone(), connection().0);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn replays_missed_dispatches() {
+        let registry = SessionRegistry::new();
+        let (sender, mut events) = connection();
+        let session = register(&registry, sender.clone());
+
+        for _ in 0..3 {
+            registry.dispatch(session, Event::Resumed);
+        }
+        assert_eq!(received(&mut events), [Some(1), Some(2), Some(3)]);
+
+        // Dispatched while disconnected
+        registry.disconnect(session, &sender);
+        for _ in 0..2 {
+            registry.dispatch(session, Event::Resumed);
+        }
+        // Not a dispatch, so there's nothing to replay
+        registry.dispatch(session, Event::GatewayHeartbeat(0));
+        assert_eq!(registry.sequence(session), Some(5));
+
+        let (sender, mut events) = connection();
+        registry
+            .resume(&resume(session, "token", 2), sender.clone())
+            .unwrap();
+        // Followed by RESUMED
+        assert_eq!(received(&mut events), [Some(3), Some(4), Some(5), Some(6)]);
+
+        registry.disconnect(session, &sender);
+        let (sender, mut events) = connection();
+        registry
+            .resume(&resume(session, "token", 6), sender)
+            .unwrap();
+        assert_eq!(received(&mut events), [Some(7)]);
+    }
+
+    #[test]
+    fn invalidates_bad_resumes() {
+        let registry = SessionRegistry::with_limits(2, RESUME_TIMEOUT);
+        let session = register(&registry, connection().0);
+
+        for _ in 0..5 {
+            registry.dispatch(session, Event::Resumed);
+        }
+
+        let resume =
+            |session, token, seq| registry.resume(&resume(session, token, seq), connection().0);
+        assert_eq!(
+            resume(Id::new(42), "token", 5),
+            Err(ResumeError::UnknownSession)
+        );
+        assert_eq!(resume(session, "other", 5), Err(ResumeError::InvalidToken));
+        assert_eq!(
+            resume(session, "token", 6),
+            Err(ResumeError::InvalidSequence)
+        );
+        assert_eq!(
+            resume(session, "token", 2),
+            Err(ResumeError::WindowExceeded)
+        );
+        assert!(resume(session, "token", 3).is_ok());
+    }
+
+    #[test]
+    fn forgets_timed_out_sessions() {
+        let registry = SessionRegistry::with_limits(REPLAY_CAPACITY, Duration::ZERO);
+        let (sender, _events) = connection();
+        let session = register(&registry, sender.clone());
+
+        registry.disconnect(session, &sender);
+        assert_eq!(
+            registry.resume(&resume(session, "token", 0), connection().0),
+            Err(ResumeError::UnknownSession)
+        );
+    }
+
+    #[test]
+    fn forgets_timed_out_sessions_without_a_resume() {
+        let registry = SessionRegistry::with_limits(REPLAY_CAPACITY, Duration::ZERO);
+        let (sender, _events) = connection();
+        let session = register(&registry, sender.clone());
+        let guild = Topic::Guild(Id::new(1));
+        registry.subscribe(session, guild);
+
+        registry.disconnect(session, &sender);
+        registry.publish(guild, &Event::Resumed);
+        assert_eq!(registry.user(session), None);
+        assert!(registry.lock().subscribers.is_empty());
+    }
+
+    #[test]
+    fn only_the_attached_connection_disconnects() {
+        let registry = SessionRegistry::with_limits(REPLAY_CAPACITY, Duration::ZERO);
+        let (old, _old_events) = connection();
+        let session = register(&registry, old.clone());
+
+        // Resumed before the old connection noticed it's gone
+        let (new, mut events) = connection();
+        registry.resume(&resume(session, "token", 0), new).unwrap();
+        assert!(!registry.disconnect(session, &old));
+
+        registry.dispatch(session, Event::Resumed);
+        assert_eq!(received(&mut events), [Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn resuming_closes_the_old_connection() {
+        let registry = SessionRegistry::new();
+        let (old, mut old_events) = connection();
+        let session = register(&registry, old.clone());
+
+        let (new, mut events) = connection();
+        registry.resume(&resume(session, "token", 0), new).unwrap();
+
+        // The old connection is told to close and gets nothing else
+        tokio::time::timeout(Duration::from_secs(1), old.replaced())
+            .await
+            .unwrap();
+        registry.dispatch(session, Event::Resumed);
+        assert_eq!(received(&mut old_events), []);
+        assert_eq!(received(&mut events), [Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn publishes_to_subscribers() {
+        let registry = SessionRegistry::new();
+        let (first, mut first_events) = connection();
+        let (second, mut second_events) = connection();
+        let first = register(&registry, first);
+        let second = register(&registry, second);
+
+        let guild = Topic::Guild(Id::new(1));
+        registry.subscribe(first, guild);
+        registry.subscribe(second, guild);
+        registry.subscribe(second, Topic::User(Id::new(2)));
+
+        registry.publish(guild, &Event::Resumed);
+        registry.publish(Topic::User(Id::new(2)), &Event::Resumed);
+        registry.publish(Topic::Channel(Id::new(1)), &Event::Resumed);
+
+        assert_eq!(received(&mut first_events), [Some(1)]);
+        assert_eq!(received(&mut second_events), [Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn lists_connected_sessions_per_user() {
+        let registry = SessionRegistry::new();
+        let (sender, _events) = connection();
+        let first = register(&registry, sender.clone());
+        let second = register(&registry, connection().0);
+
+        let sessions = |registry: &SessionRegistry| {
+            registry
+                .sessions(Id::new(1))
+                .into_iter()
+                .map(|s| s.session_id)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(sessions(&registry), HashSet::from([first, second]));
+
+        let mut idle = registry.presences(Id::new(1))[0].clone();
+        idle.status = Status::Idle;
+        registry.update_presence(second, idle);
+
+        registry.disconnect(first, &sender);
+        assert_eq!(sessions(&registry), HashSet::from([second]));
+        assert_eq!(registry.sessions(Id::new(1))[0].status, Status::Idle);
+        assert_eq!(registry.presences(Id::new(1)).len(), 1);
+        assert!(registry.sessions(Id::new(2)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn detaches_slow_connections() {
+        let registry = SessionRegistry::new();
+        let (sender, mut events) = EventSender::new(2);
+        let session = register(&registry, sender.clone());
+
+        for _ in 0..4 {
+            registry.dispatch(session, Event::Resumed);
+        }
+
+        // The connection is told to close and gets nothing else
+        tokio::time::timeout(Duration::from_secs(1), sender.overflowed())
+            .await
+            .unwrap();
+        assert_eq!(received(&mut events), [Some(1), Some(2)]);
+
+        // Everything it missed is still there for resuming
+        let (sender, mut events) = connection();
+        registry
+            .resume(&resume(session, "token", 2), sender)
+            .unwrap();
+        assert_eq!(received(&mut events), [Some(3), Some(4), Some(5)]);
+    }
+}
@@ -0,0 +1,335 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::permissions::channel_permissions;
+use radiance::events::VoiceStateUpdate;
+use radiance::store::StoredGuild;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use twilight_model::channel::ChannelType;
+use twilight_model::gateway::payload::incoming::VoiceServerUpdate;
+use twilight_model::guild::Permissions;
+use twilight_model::id::marker::{GuildMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::voice::VoiceState;
+
+/// Where clients connect for voice.
+#[derive(Clone, Debug)]
+pub struct VoiceConfig {
+    pub endpoint: String,
+}
+
+impl VoiceConfig {
+    /// Reads `RADIANCE_VOICE_ENDPOINT`, falling back to a voice server on
+    /// this machine.
+    pub fn from_env() -> Self {
+        VoiceConfig {
+            endpoint: std::env::var("RADIANCE_VOICE_ENDPOINT")
+                .unwrap_or_else(|_| "localhost:4435".to_string()),
+        }
+    }
+
+    /// A voice server for `guild_id`, with a fresh token to connect with.
+    pub fn server(&self, guild_id: Id<GuildMarker>) -> VoiceServerUpdate {
+        let mut token = [0; 8];
+        // The voice server doesn't check tokens yet, so any will do
+        let _ = SystemRandom::new().fill(&mut token);
+
+        VoiceServerUpdate {
+            endpoint: Some(self.endpoint.clone()),
+            guild_id,
+            token: token.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum VoiceError {
+    UnknownChannel,
+    NotVoiceChannel,
+    MissingPermissions,
+    // Only the session that's in voice can leave
+    NotConnected,
+}
+
+pub struct VoiceChange {
+    pub state: VoiceState,
+    // The session wasn't in a voice channel before, so needs a voice server
+    pub connected: bool,
+    // Another session of the user that was in voice, and now isn't
+    pub replaced: Option<VoiceState>,
+}
+
+type GuildStates = HashMap<Id<GuildMarker>, HashMap<Id<UserMarker>, VoiceState>>;
+
+/// Who's in which voice channel, per guild.
+#[derive(Clone, Default)]
+pub struct VoiceStates {
+    guilds: Arc<Mutex<GuildStates>>,
+}
+
+impl VoiceStates {
+    pub fn guild(&self, guild_id: Id<GuildMarker>) -> Vec<VoiceState> {
+        self.lock()
+            .get(&guild_id)
+            .map(|states| states.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Moves `user_id` to the requested channel of `guild`, or out of voice
+    /// if there is none. Users have to be able to see and connect to voice
+    /// channels to join them, and are only ever in voice from one session.
+    /// Joining from another session replaces the previous one.
+    pub fn update(
+        &self,
+        guild: &StoredGuild,
+        user_id: Id<UserMarker>,
+        session_id: &str,
+        request: &VoiceStateUpdate,
+    ) -> Result<VoiceChange, VoiceError> {
+        let guild_id = guild.guild.id;
+        let member = guild
+            .members
+            .iter()
+            .find(|m| m.user.id == user_id)
+            .ok_or(VoiceError::MissingPermissions)?;
+
+        let mut state = VoiceState {
+            channel_id: None,
+            deaf: member.deaf,
+            guild_id: Some(guild_id),
+            member: Some(member.clone()),
+            mute: member.mute,
+            self_deaf: request.self_deaf,
+            self_mute: request.self_mute,
+            self_stream: false,
+            self_video: request.self_video,
+            session_id: session_id.to_string(),
+            suppress: false,
+            user_id,
+            request_to_speak_timestamp: None,
+        };
+
+        let Some(channel_id) = request.channel_id else {
+            let mut guilds = self.lock();
+            let states = guilds.get_mut(&guild_id).ok_or(VoiceError::NotConnected)?;
+            if states.get(&user_id).map(|s| s.session_id.as_str()) != Some(session_id) {
+                return Err(VoiceError::NotConnected);
+            }
+            states.remove(&user_id);
+
+            return Ok(VoiceChange {
+                state,
+                connected: false,
+                replaced: None,
+            });
+        };
+
+        let channel = guild
+            .guild
+            .channels
+            .iter()
+            .find(|c| c.id == channel_id)
+            .ok_or(VoiceError::UnknownChannel)?;
+        if !matches!(
+            channel.kind,
+            ChannelType::GuildVoice | ChannelType::GuildStageVoice
+        ) {
+            return Err(VoiceError::NotVoiceChannel);
+        }
+        let required = Permissions::VIEW_CHANNEL | Permissions::CONNECT;
+        if !channel_permissions(&guild.guild, member, channel).contains(required) {
+            return Err(VoiceError::MissingPermissions);
+        }
+
+        state.channel_id = Some(channel_id);
+        // Everyone starts out in the audience on stages
+        state.suppress = channel.kind == ChannelType::GuildStageVoice;
+
+        let previous = self
+            .lock()
+            .entry(guild_id)
+            .or_default()
+            .insert(user_id, state.clone());
+
+        let (connected, replaced) = match previous {
+            Some(previous) if previous.session_id == session_id => (false, None),
+            previous => (
+                true,
+                previous.map(|previous| VoiceState {
+                    channel_id: None,
+                    ..previous
+                }),
+            ),
+        };
+
+        Ok(VoiceChange {
+            state,
+            connected,
+            replaced,
+        })
+    }
+
+    /// Takes the session out of voice everywhere, returning the states to
+    /// tell guilds about.
+    pub fn disconnect(&self, session_id: &str) -> Vec<VoiceState> {
+        let mut left = vec![];
+
+        for states in self.lock().values_mut() {
+            states.retain(|_, state| {
+                if state.session_id != session_id {
+                    return true;
+                }
+
+                left.push(VoiceState {
+                    channel_id: None,
+                    ..state.clone()
+                });
+                false
+            });
+        }
+
+        left
+    }
+
+    fn lock(&self) -> MutexGuard<'_, GuildStates> {
+        // States are replaced whole, so a poisoned lock is fine
+        self.guilds.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radiance::store::AccountStore;
+
+    const PERSON: Id<UserMarker> = Id::new(967478107550388235);
+    const FRIEND: Id<UserMarker> = Id::new(1209484625727361044);
+    const TEXT: u64 = 1201246479711178802;
+    const VOICE: u64 = 1201246479711178803;
+
+    fn guild() -> StoredGuild {
+        let store = AccountStore::from_json(include_str!("../store.example.json")).unwrap();
+
+        store
+            .guild(PERSON, Id::new(1201246479224631326))
+            .unwrap()
+            .clone()
+    }
+
+    fn request(channel_id: Option<u64>) -> VoiceStateUpdate {
+        VoiceStateUpdate {
+            guild_id: Some(Id::new(1201246479224631326)),
+            channel_id: channel_id.map(Id::new),
+            self_mute: true,
+            self_deaf: false,
+            self_video: false,
+            flags: 0,
+        }
+    }
+
+    #[test]
+    fn joins_and_leaves_voice_channels() {
+        let guild = guild();
+        let voice = VoiceStates::default();
+
+        let change = voice
+            .update(&guild, PERSON, "a", &request(Some(VOICE)))
+            .unwrap();
+        assert!(change.connected);
+        assert_eq!(change.state.channel_id, Some(Id::new(VOICE)));
+        assert!(change.state.self_mute);
+
+        // Changing state in the same channel doesn't need a new server
+        let change = voice
+            .update(&guild, PERSON, "a", &request(Some(VOICE)))
+            .unwrap();
+        assert!(!change.connected);
+        voice
+            .update(&guild, FRIEND, "b", &request(Some(VOICE)))
+            .unwrap();
+        assert_eq!(voice.guild(guild.guild.id).len(), 2);
+
+        let change = voice.update(&guild, PERSON, "a", &request(None)).unwrap();
+        assert_eq!(change.state.channel_id, None);
+        assert_eq!(voice.guild(guild.guild.id).len(), 1);
+        assert_eq!(
+            voice.update(&guild, PERSON, "a", &request(None)).err(),
+            Some(VoiceError::NotConnected)
+        );
+
+        let left = voice.disconnect("b");
+        assert_eq!((left.len(), left[0].user_id), (1, FRIEND));
+        assert!(voice.guild(guild.guild.id).is_empty());
+    }
+
+    #[test]
+    fn replaces_other_sessions() {
+        let guild = guild();
+        let voice = VoiceStates::default();
+
+        voice
+            .update(&guild, PERSON, "a", &request(Some(VOICE)))
+            .unwrap();
+        let change = voice
+            .update(&guild, PERSON, "b", &request(Some(VOICE)))
+            .unwrap();
+        assert!(change.connected);
+        let replaced = change.replaced.unwrap();
+        assert_eq!(
+            (replaced.session_id.as_str(), replaced.channel_id),
+            ("a", None)
+        );
+
+        // The replaced session can't take the new one out of voice
+        assert_eq!(
+            voice.update(&guild, PERSON, "a", &request(None)).err(),
+            Some(VoiceError::NotConnected)
+        );
+        assert!(voice.disconnect("a").is_empty());
+        assert_eq!(voice.guild(guild.guild.id)[0].session_id, "b");
+
+        let change = voice.update(&guild, PERSON, "b", &request(None)).unwrap();
+        assert!(change.replaced.is_none());
+        assert!(voice.guild(guild.guild.id).is_empty());
+    }
+
+    #[test]
+    fn validates_channels() {
+        let mut guild = guild();
+        let voice = VoiceStates::default();
+
+        assert_eq!(
+            voice
+                .update(&guild, FRIEND, "b", &request(Some(TEXT)))
+                .err(),
+            Some(VoiceError::NotVoiceChannel)
+        );
+        assert_eq!(
+            voice.update(&guild, FRIEND, "b", &request(Some(42))).err(),
+            Some(VoiceError::UnknownChannel)
+        );
+
+        // Without CONNECT for @everyone, only the owner can join
+        for role in &mut guild.guild.roles {
+            role.permissions -= Permissions::CONNECT;
+        }
+        assert_eq!(
+            voice
+                .update(&guild, FRIEND, "b", &request(Some(VOICE)))
+                .err(),
+            Some(VoiceError::MissingPermissions)
+        );
+        assert!(voice
+            .update(&guild, PERSON, "a", &request(Some(VOICE)))
+            .is_ok());
+        assert!(voice
+            .guild(guild.guild.id)
+            .iter()
+            .all(|s| s.user_id == PERSON));
+    }
+}
@@ -7,14 +7,35 @@
 use crate::version::{Platform, Version};
 use crate::VERSION_MAP;
 use log::{debug, warn};
+use radiance::bus::{BusEvent, EventBus, Topic};
+use radiance::events::models;
+use radiance::store::AccountStore;
 use reqwest::ClientBuilder;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
+use twilight_model::channel::message::MessageType;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::ChannelMarker;
+use twilight_model::id::Id;
+use twilight_model::user::User;
+use twilight_model::util::Timestamp;
 use warp::http::Response;
 
+// First second of 2015, which snowflakes count from
+const DISCORD_EPOCH: u64 = 1420070400000;
+
+#[derive(Deserialize)]
+pub struct CreateMessage {
+    content: String,
+    #[serde(default)]
+    tts: bool,
+}
+
 pub async fn forward(
     path: warp::path::FullPath,
     p: HashMap<String, String>,
@@ -128,3 +149,163 @@ pub fn updates_stable(p: HashMap<String, String>) -> warp::http::Result<Response
 
     res
 }
+
+/// Sends a message as whoever `authorization` belongs to, to a channel of one of
+/// their guilds or one of their private channels.
+pub fn create_message<B: EventBus>(
+    channel_id: Id<ChannelMarker>,
+    authorization: Option<String>,
+    body: CreateMessage,
+    store: AccountStore,
+    bus: Arc<B>,
+) -> warp::http::Result<Response<String>> {
+    let builder = Response::builder()
+        .header("Date", httpdate::fmt_http_date(SystemTime::now()))
+        .header("Content-Type", "application/json");
+
+    let Some(account) = authorization.and_then(|token| store.authenticate(&token)) else {
+        return builder.status(401).body(String::from(
+            r#"{"message": "401: Unauthorized", "code": 0}"#,
+        ));
+    };
+
+    let guild = store.channel_guild(account.user.id, channel_id);
+    let private = account.private_channels.iter().any(|c| c.id == channel_id);
+    if guild.is_none() && !private {
+        return builder.status(404).body(String::from(
+            r#"{"message": "Unknown Channel", "code": 10003}"#,
+        ));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let message = Message {
+        activity: None,
+        application: None,
+        application_id: None,
+        attachments: vec![],
+        author: author(&account.user),
+        channel_id,
+        components: vec![],
+        content: body.content,
+        edited_timestamp: None,
+        embeds: vec![],
+        flags: None,
+        guild_id: guild.map(|g| g.guild.id),
+        id: Id::new(snowflake(now.as_millis() as u64)),
+        interaction: None,
+        kind: MessageType::Regular,
+        member: None,
+        mention_channels: vec![],
+        mention_everyone: false,
+        mention_roles: vec![],
+        mentions: vec![],
+        pinned: false,
+        reactions: vec![],
+        reference: None,
+        referenced_message: None,
+        role_subscription_data: None,
+        sticker_items: vec![],
+        timestamp: Timestamp::from_micros(now.as_micros() as i64)
+            .expect("Current time is a valid timestamp"),
+        thread: None,
+        tts: body.tts,
+        webhook_id: None,
+    };
+
+    let body = serde_json::to_string(&message).unwrap_or_default();
+
+    match BusEvent::new(Topic::Channel(channel_id), "MESSAGE_CREATE", &message) {
+        Ok(event) => bus.publish(event),
+        Err(e) => warn!("Can't publish message {}: {e}", message.id),
+    }
+
+    builder.body(body)
+}
+
+// What everyone else sees of an account
+fn author(user: &models::User) -> User {
+    User {
+        accent_color: user.accent_color,
+        avatar: user.avatar,
+        avatar_decoration: user.avatar_decoration_data,
+        banner: user.banner,
+        bot: false,
+        discriminator: user.discriminator.parse().unwrap_or_default(),
+        email: None,
+        flags: None,
+        global_name: user.global_name.clone(),
+        id: user.id,
+        locale: None,
+        mfa_enabled: None,
+        name: user.username.clone(),
+        premium_type: None,
+        public_flags: None,
+        system: None,
+        verified: None,
+    }
+}
+
+// Snowflakes made in the same millisecond are told apart by the low 12 bits
+fn snowflake(millis: u64) -> u64 {
+    static INCREMENT: AtomicU64 = AtomicU64::new(0);
+
+    let increment = INCREMENT.fetch_add(1, Ordering::Relaxed) & 0xfff;
+    (millis.saturating_sub(DISCORD_EPOCH) << 22 | increment).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use radiance::bus::LocalBus;
+    use twilight_model::id::marker::UserMarker;
+
+    const PERSON: Id<UserMarker> = Id::new(967478107550388235);
+    const TEXT: Id<ChannelMarker> = Id::new(1201246479711178802);
+
+    fn store() -> AccountStore {
+        let json = include_str!("../store.example.json").replace("change-me", "secret");
+        AccountStore::from_json(&json).unwrap()
+    }
+
+    fn send(authorization: Option<String>, channel_id: Id<ChannelMarker>) -> Response<String> {
+        let body = CreateMessage {
+            content: "hi".to_string(),
+            tts: false,
+        };
+
+        create_message(
+            channel_id,
+            authorization,
+            body,
+            store(),
+            Arc::new(LocalBus::new()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sends_messages_as_the_token_owner() {
+        let response = send(store().token(PERSON), TEXT);
+        assert_eq!(response.status(), 200);
+
+        let message = serde_json::from_str::<Message>(response.body()).unwrap();
+        assert_eq!(message.author.id, PERSON);
+        assert_eq!(message.guild_id, Some(Id::new(1201246479224631326)));
+    }
+
+    #[test]
+    fn rejects_unknown_tokens_and_channels() {
+        assert_eq!(send(None, TEXT).status(), 401);
+        assert_eq!(send(Some("nope".to_string()), TEXT).status(), 401);
+        assert_eq!(send(store().token(PERSON), Id::new(1)).status(), 404);
+    }
+
+    #[test]
+    fn snowflakes_are_unique() {
+        assert_ne!(snowflake(DISCORD_EPOCH + 1), snowflake(DISCORD_EPOCH + 1));
+        assert_eq!(snowflake(DISCORD_EPOCH + 1) >> 22, 1);
+    }
+}
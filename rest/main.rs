@@ -7,15 +7,19 @@
 mod endpoints;
 mod version;
 
-use crate::endpoints::{forward, updates_stable};
+use crate::endpoints::{create_message, forward, updates_stable};
 use crate::version::{Platform, Version};
 use enum_map::{enum_map, EnumMap};
 use hickory_resolver::config::{ResolverConfig, ResolverOpts};
 use hickory_resolver::TokioAsyncResolver;
 use once_cell::unsync::Lazy;
+use radiance::bus::{self, SocketBus};
+use radiance::store::AccountStore;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use twilight_model::id::marker::ChannelMarker;
+use twilight_model::id::Id;
 use warp::Filter;
 
 const OSX_VERSION: Version = Version::new(0, 0, 295);
@@ -26,6 +30,9 @@ const WIN_PUB_DATE: &'static str = "2021-09-22T18:16:06";
 const LINUX_VERSION: Version = Version::new(0, 0, 43);
 const LINUX_PUB_DATE: &'static str = "2024-02-12T21:18:51";
 
+// Message content is capped at 4000 characters, this leaves room for embeds
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
 const VERSION_MAP: Lazy<EnumMap<Platform, (Version, &'static str)>> = Lazy::new(|| {
     enum_map! {
         Platform::OsX => (OSX_VERSION, OSX_PUB_DATE),
@@ -55,6 +62,21 @@ async fn main() {
         .and(warp::query::<HashMap<String, String>>())
         .map(updates_stable);
 
+    // Events are handed to the gateway, which dispatches them to clients
+    let bus = Arc::new(SocketBus::connect(bus::socket_path()));
+    // Shared with the gateway, which signs the tokens
+    let store = AccountStore::load(&AccountStore::path()).expect("Can't load the account store");
+
+    let create_message = warp::path!("api" / "v9" / "channels" / ..)
+        .and(warp::path::param::<Id<ChannelMarker>>())
+        .and(warp::path!("messages"))
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::json())
+        .and(warp::any().map(move || store.clone()))
+        .and(warp::any().map(move || bus.clone()))
+        .map(create_message);
+
     // Match any request and return hello world!
     let routes = warp::any()
         .and(warp::path::full()) // Extract the full path
@@ -62,10 +84,15 @@ async fn main() {
         .and(warp::any().map(move || addresses.clone()))
         .and_then(forward);
 
-    warp::serve(warp::get().and(updates_stable).or(routes))
-        .tls()
-        .cert_path("certs/cert.rest.pem")
-        .key_path("certs/key.rest.pem")
-        .run(([0, 0, 0, 0], 4433))
-        .await;
+    warp::serve(
+        warp::get()
+            .and(updates_stable)
+            .or(warp::post().and(create_message))
+            .or(routes),
+    )
+    .tls()
+    .cert_path("certs/cert.rest.pem")
+    .key_path("certs/key.rest.pem")
+    .run(([0, 0, 0, 0], 4433))
+    .await;
 }
@@ -0,0 +1,100 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use eetf::Term;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+// Roughly the shape of the guild members in a READY payload
+
+#[derive(Deserialize, Serialize)]
+struct User {
+    id: u64,
+    username: String,
+    global_name: Option<String>,
+    avatar: Option<String>,
+    discriminator: String,
+    bot: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Member {
+    user: User,
+    nick: Option<String>,
+    roles: Vec<u64>,
+    joined_at: String,
+    deaf: bool,
+    mute: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Payload {
+    op: u8,
+    t: String,
+    s: u32,
+    d: Vec<Member>,
+}
+
+fn payload(members: u64) -> Payload {
+    Payload {
+        op: 0,
+        t: "READY".to_string(),
+        s: 1,
+        d: (0..members)
+            .map(|i| Member {
+                user: User {
+                    id: 1 << 60 | i,
+                    username: format!("user{i}"),
+                    global_name: (i % 2 == 0).then(|| format!("User {i}")),
+                    avatar: Some("a_0123456789abcdef0123456789abcdef".to_string()),
+                    discriminator: "0".to_string(),
+                    bot: false,
+                },
+                nick: None,
+                roles: vec![1 << 59 | i, 1 << 58 | i],
+                joined_at: "2024-02-20T21:45:24.000000+00:00".to_string(),
+                deaf: false,
+                mute: false,
+            })
+            .collect(),
+    }
+}
+
+fn serialize(c: &mut Criterion) {
+    let payload = payload(5000);
+    let mut group = c.benchmark_group("serialize");
+
+    group.bench_function("to_term", |b| {
+        b.iter(|| {
+            let mut buf = Vec::new();
+            serde_etf::to_term(black_box(&payload))
+                .unwrap()
+                .encode(&mut buf)
+                .unwrap();
+            buf
+        })
+    });
+    group.bench_function("to_vec", |b| {
+        b.iter(|| serde_etf::to_vec(black_box(&payload)).unwrap())
+    });
+
+    group.finish();
+}
+
+fn deserialize(c: &mut Criterion) {
+    let bytes = serde_etf::to_vec(&payload(5000)).unwrap();
+    let mut group = c.benchmark_group("deserialize");
+
+    group.bench_function("from_term", |b| {
+        b.iter(|| {
+            let term = Term::decode(Cursor::new(black_box(&bytes))).unwrap();
+            serde_etf::from_term::<Payload>(term).unwrap()
+        })
+    });
+    group.bench_function("from_slice", |b| {
+        b.iter(|| serde_etf::from_slice::<Payload>(black_box(&bytes)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, serialize, deserialize);
+criterion_main!(benches);
@@ -1,18 +1,29 @@
 use eetf::{BigInteger, Term};
 use std::collections::HashMap;
 
-use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::de::{
+    self, value, DeserializeSeed, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
 use serde::{forward_to_deserialize_any, Deserialize};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, Segment};
+use crate::options::{Options, DEFAULT};
+use crate::tag;
 
-pub struct Deserializer {
+pub struct Deserializer<'a> {
     input: Term,
+    options: &'a Options,
 }
 
-impl Deserializer {
+impl Deserializer<'static> {
     pub fn from_term(input: Term) -> Self {
-        Deserializer { input }
+        Deserializer::from_term_with(input, &DEFAULT)
+    }
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn from_term_with(input: Term, options: &'a Options) -> Self {
+        Deserializer { input, options }
     }
 }
 
@@ -20,42 +31,64 @@ pub fn from_term<T>(t: Term) -> Result<T>
 where
     T: for<'de> Deserialize<'de>,
 {
-    let deserializer = Deserializer::from_term(t);
+    from_term_with(t, &DEFAULT)
+}
+
+pub fn from_term_with<T>(t: Term, options: &Options) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let deserializer = Deserializer::from_term_with(t, options);
     T::deserialize(deserializer)
 }
 
-impl Deserializer {
+impl Deserializer<'_> {
     fn deserialize_integer<'de, V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         match self.input {
             Term::FixInteger(i) => visitor.visit_i32(i.value),
-            Term::BigInteger(i) => Self::deserialize_bigint(i, visitor),
+            Term::BigInteger(i) => Self::deserialize_bigint(i, false, visitor),
+            _ => Err(Error::ExpectedInt),
+        }
+    }
+
+    fn deserialize_integer128<'de, V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.input {
+            Term::FixInteger(i) => visitor.visit_i32(i.value),
+            Term::BigInteger(i) => Self::deserialize_bigint(i, true, visitor),
             _ => Err(Error::ExpectedInt),
         }
     }
 
-    fn deserialize_bigint<'de, V>(i: BigInteger, visitor: V) -> Result<V::Value>
+    // Like the byte decoder, only 128 bit integers and `deserialize_any` get
+    // more than 64 bits
+    fn deserialize_bigint<'de, V>(i: BigInteger, wide: bool, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Ok(num) = i.to_string().parse::<i64>() {
+        let num = i.to_string();
+        if let Ok(num) = num.parse::<i64>() {
             visitor.visit_i64(num)
-        } else if !i.to_string().starts_with('-') {
-            if let Ok(num) = i.to_string().parse::<u64>() {
-                visitor.visit_u64(num)
-            } else {
-                Err(Error::NumberTooLarge)
-            }
-        } else {
-            // Number is negative, but doesn't fit into i64
+        } else if let Ok(num) = num.parse::<u64>() {
+            visitor.visit_u64(num)
+        } else if let (true, Ok(num)) = (wide, num.parse::<i128>()) {
+            visitor.visit_i128(num)
+        } else if let (true, Ok(num)) = (wide, num.parse::<u128>()) {
+            visitor.visit_u128(num)
+        } else if num.starts_with('-') {
             Err(Error::NumberTooSmall)
+        } else {
+            Err(Error::NumberTooLarge)
         }
     }
 }
 
-impl<'de> de::Deserializer<'de> for Deserializer {
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -63,22 +96,35 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         V: Visitor<'de>,
     {
         match self.input {
-            Term::Atom(a) => visitor.visit_string(a.name),
+            // Booleans and null are atoms, too
+            Term::Atom(a) => match &*a.name {
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                name if name == self.options.null_atom => visitor.visit_unit(),
+                _ => visitor.visit_string(a.name),
+            },
             Term::FixInteger(i) => visitor.visit_i32(i.value),
-            Term::BigInteger(i) => Self::deserialize_bigint(i, visitor),
+            Term::BigInteger(i) => Self::deserialize_bigint(i, true, visitor),
             Term::Float(f) => visitor.visit_f64(f.value),
             Term::Pid(_) => Err(Error::InvalidInput),
             Term::Port(_) => Err(Error::InvalidInput),
             Term::Reference(_) => Err(Error::InvalidInput),
             Term::ExternalFun(_) => Err(Error::InvalidInput),
             Term::InternalFun(_) => Err(Error::InvalidInput),
-            Term::Binary(b) => visitor.visit_byte_buf(b.bytes),
+            // Binaries are strings, unless they aren't valid UTF-8
+            Term::Binary(b) => match String::from_utf8(b.bytes) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
             Term::BitBinary(_) => Err(Error::InvalidInput),
-            Term::ByteList(_) => Err(Error::InvalidInput),
-            Term::List(l) => visitor.visit_seq(SeqDeserializer::new(l.elements)),
-            Term::ImproperList(_) => Err(Error::InvalidInput),
-            Term::Tuple(t) => visitor.visit_seq(SeqDeserializer::new(t.elements)),
-            Term::Map(m) => visitor.visit_map(MapDeserializer::new(m.map)),
+            // Without a type to go by, byte lists are lists of integers
+            Term::ByteList(b) => {
+                visitor.visit_seq(value::SeqDeserializer::new(b.bytes.into_iter()))
+            }
+            Term::List(l) => visitor.visit_seq(SeqDeserializer::new(l.elements, self.options)),
+            Term::ImproperList(_) => Err(Error::ImproperList),
+            Term::Tuple(t) => visitor.visit_seq(SeqDeserializer::new(t.elements, self.options)),
+            Term::Map(m) => visitor.visit_map(MapDeserializer::new(m.map, self.options)),
         }
     }
 
@@ -92,6 +138,11 @@ impl<'de> de::Deserializer<'de> for Deserializer {
                 "true" => visitor.visit_bool(true),
                 _ => Err(Error::ExpectedBool),
             },
+            Term::FixInteger(i) if !self.options.bool_atoms => match i.value {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                _ => Err(Error::ExpectedBool),
+            },
             _ => Err(Error::ExpectedBool),
         }
     }
@@ -124,6 +175,13 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer128(visitor)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -152,6 +210,13 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         self.deserialize_integer(visitor)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer128(visitor)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -182,6 +247,11 @@ impl<'de> de::Deserializer<'de> for Deserializer {
     {
         match self.input {
             Term::Binary(v) => visitor.visit_byte_buf(v.bytes),
+            Term::Atom(a) => visitor.visit_string(a.name),
+            // Each byte is a code point, so they're Latin-1 like atoms
+            Term::ByteList(b) => {
+                visitor.visit_string(b.bytes.iter().map(|&b| char::from(b)).collect())
+            }
             _ => Err(Error::ExpectedString),
         }
     }
@@ -206,6 +276,7 @@ impl<'de> de::Deserializer<'de> for Deserializer {
     {
         match self.input {
             Term::Binary(b) => visitor.visit_byte_buf(b.bytes),
+            Term::ByteList(b) => visitor.visit_byte_buf(b.bytes),
             _ => Err(Error::ExpectedBytes),
         }
     }
@@ -215,22 +286,19 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         V: Visitor<'de>,
     {
         match self.input {
-            Term::Atom(ref a) => {
-                if a.name == "nil" {
-                    visitor.visit_none()
-                } else {
-                    Err(Error::InvalidInput)
-                }
-            }
+            Term::Atom(ref a) if a.name == self.options.null_atom => visitor.visit_none(),
             _ => visitor.visit_some(self),
         }
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        match self.input {
+            Term::Atom(a) if a.name == self.options.null_atom => visitor.visit_unit(),
+            _ => Err(Error::ExpectedNil),
+        }
     }
 
     fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -252,7 +320,11 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         V: Visitor<'de>,
     {
         match self.input {
-            Term::List(l) => visitor.visit_seq(SeqDeserializer::new(l.elements)),
+            Term::List(l) => visitor.visit_seq(SeqDeserializer::new(l.elements, self.options)),
+            Term::ByteList(b) => {
+                visitor.visit_seq(value::SeqDeserializer::new(b.bytes.into_iter()))
+            }
+            Term::ImproperList(_) => Err(Error::ImproperList),
             _ => Err(Error::ExpectedList),
         }
     }
@@ -266,7 +338,7 @@ impl<'de> de::Deserializer<'de> for Deserializer {
                 if t.elements.len() != len {
                     return Err(Error::WrongTupleLength);
                 }
-                visitor.visit_seq(SeqDeserializer::new(t.elements))
+                visitor.visit_seq(SeqDeserializer::new(t.elements, self.options))
             }
             _ => Err(Error::ExpectedTuple),
         }
@@ -292,7 +364,7 @@ impl<'de> de::Deserializer<'de> for Deserializer {
         V: Visitor<'de>,
     {
         match self.input {
-            Term::Map(m) => visitor.visit_map(MapDeserializer::new(m.map)),
+            Term::Map(m) => visitor.visit_map(MapDeserializer::new(m.map, self.options)),
             _ => Err(Error::ExpectedMap),
         }
     }
@@ -318,7 +390,22 @@ impl<'de> de::Deserializer<'de> for Deserializer {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(EnumDeserializer::new(self.input))
+        match self.input {
+            // Unit variants are encoded by name
+            Term::Binary(_) | Term::Atom(_) => {
+                visitor.visit_enum(EnumDeserializer::new(self.input, None, self.options))
+            }
+            // Other variants are encoded as a map with a single entry from the
+            // variant name to its data
+            Term::Map(m) => {
+                if m.map.len() != 1 {
+                    return Err(Error::ExpectedEnum);
+                }
+                let (variant, value) = m.map.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer::new(variant, Some(value), self.options))
+            }
+            _ => Err(Error::ExpectedEnum),
+        }
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -336,19 +423,21 @@ impl<'de> de::Deserializer<'de> for Deserializer {
     }
 }
 
-struct SeqDeserializer {
-    iter: <Vec<Term> as IntoIterator>::IntoIter,
+struct SeqDeserializer<'a> {
+    iter: std::iter::Enumerate<<Vec<Term> as IntoIterator>::IntoIter>,
+    options: &'a Options,
 }
 
-impl SeqDeserializer {
-    fn new(vec: Vec<Term>) -> Self {
+impl<'a> SeqDeserializer<'a> {
+    fn new(vec: Vec<Term>, options: &'a Options) -> Self {
         SeqDeserializer {
-            iter: vec.into_iter(),
+            iter: vec.into_iter().enumerate(),
+            options,
         }
     }
 }
 
-impl<'de> de::Deserializer<'de> for SeqDeserializer {
+impl<'de> de::Deserializer<'de> for SeqDeserializer<'_> {
     type Error = Error;
 
     #[inline]
@@ -376,7 +465,7 @@ impl<'de> de::Deserializer<'de> for SeqDeserializer {
     }
 }
 
-impl<'de> SeqAccess<'de> for SeqDeserializer {
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -384,27 +473,35 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some(value) => seed.deserialize(Deserializer::from_term(value)).map(Some),
+            Some((index, value)) => {
+                let tag = tag::of(&value);
+                seed.deserialize(Deserializer::from_term_with(value, self.options))
+                    .map(Some)
+                    .map_err(|e| e.at(Segment::Index(index), Some(tag)))
+            }
             None => Ok(None),
         }
     }
 }
 
-struct MapDeserializer {
-    iter: <HashMap<Term, Term> as IntoIterator>::IntoIter,
-    value: Option<Term>,
+struct MapDeserializer<'a> {
+    iter: std::iter::Enumerate<<HashMap<Term, Term> as IntoIterator>::IntoIter>,
+    // The value of the current entry, and where it is
+    value: Option<(Term, Segment)>,
+    options: &'a Options,
 }
 
-impl MapDeserializer {
-    fn new(map: HashMap<Term, Term>) -> Self {
+impl<'a> MapDeserializer<'a> {
+    fn new(map: HashMap<Term, Term>, options: &'a Options) -> Self {
         MapDeserializer {
-            iter: map.into_iter(),
+            iter: map.into_iter().enumerate(),
             value: None,
+            options,
         }
     }
 }
 
-impl<'de> MapAccess<'de> for MapDeserializer {
+impl<'de> MapAccess<'de> for MapDeserializer<'_> {
     type Error = Error;
 
     fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -412,9 +509,13 @@ impl<'de> MapAccess<'de> for MapDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some((key, value)) => {
-                self.value = Some(value);
-                seed.deserialize(Deserializer::from_term(key)).map(Some)
+            Some((index, (key, value))) => {
+                let segment = Segment::key(&key, index);
+                let tag = tag::of(&key);
+                self.value = Some((value, segment.clone()));
+                seed.deserialize(Deserializer::from_term_with(key, self.options))
+                    .map(Some)
+                    .map_err(|e| e.at(segment, Some(tag)))
             }
             None => Ok(None),
         }
@@ -425,13 +526,17 @@ impl<'de> MapAccess<'de> for MapDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some(value) => seed.deserialize(Deserializer::from_term(value)),
+            Some((value, segment)) => {
+                let tag = tag::of(&value);
+                seed.deserialize(Deserializer::from_term_with(value, self.options))
+                    .map_err(|e| e.at(segment, Some(tag)))
+            }
             None => Err(de::Error::custom("value is missing")),
         }
     }
 }
 
-impl<'de> de::Deserializer<'de> for MapDeserializer {
+impl<'de> de::Deserializer<'de> for MapDeserializer<'_> {
     type Error = Error;
 
     #[inline]
@@ -449,57 +554,115 @@ impl<'de> de::Deserializer<'de> for MapDeserializer {
     }
 }
 
-pub struct EnumDeserializer {
+pub struct EnumDeserializer<'a> {
     variant: Term,
+    value: Option<Term>,
+    options: &'a Options,
 }
 
-impl EnumDeserializer {
-    pub fn new(variant: Term) -> EnumDeserializer {
-        EnumDeserializer { variant }
+impl<'a> EnumDeserializer<'a> {
+    pub fn new(variant: Term, value: Option<Term>, options: &'a Options) -> Self {
+        EnumDeserializer {
+            variant,
+            value,
+            options,
+        }
     }
 }
 
-impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+impl<'a, 'de> de::EnumAccess<'de> for EnumDeserializer<'a> {
     type Error = Error;
-    type Variant = VariantDeserializer;
+    type Variant = VariantDeserializer<'a>;
 
     fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
     where
         V: de::DeserializeSeed<'de>,
     {
-        let visitor = VariantDeserializer {};
-        seed.deserialize(Deserializer::from_term(self.variant))
+        let visitor = VariantDeserializer {
+            segment: Segment::key(&self.variant, 0),
+            value: self.value,
+            options: self.options,
+        };
+        seed.deserialize(Deserializer::from_term_with(self.variant, self.options))
             .map(|v| (v, visitor))
     }
 }
 
-pub struct VariantDeserializer {}
+pub struct VariantDeserializer<'a> {
+    // Where the variant's data is
+    segment: Segment,
+    value: Option<Term>,
+    options: &'a Options,
+}
 
-impl<'de> VariantAccess<'de> for VariantDeserializer {
+impl VariantDeserializer<'_> {
+    fn locate(segment: Segment, value: &Term) -> impl FnOnce(Error) -> Error {
+        let tag = tag::of(value);
+        move |e| e.at(segment, Some(tag))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer<'_> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        Ok(())
+        match self.value {
+            None => Ok(()),
+            Some(_) => Err(Error::ExtraneousInput),
+        }
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        Err(Error::ExpectedTuple)
+        match self.value {
+            Some(value) => {
+                let locate = Self::locate(self.segment, &value);
+                seed.deserialize(Deserializer::from_term_with(value, self.options))
+                    .map_err(locate)
+            }
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::ExpectedTuple)
+        let Some(value) = self.value else {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            ));
+        };
+        let locate = Self::locate(self.segment, &value);
+        match value {
+            Term::List(l) => visitor.visit_seq(SeqDeserializer::new(l.elements, self.options)),
+            Term::Tuple(t) => visitor.visit_seq(SeqDeserializer::new(t.elements, self.options)),
+            _ => Err(Error::ExpectedList),
+        }
+        .map_err(locate)
     }
 
     fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::ExpectedMap)
+        let Some(value) = self.value else {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            ));
+        };
+        let locate = Self::locate(self.segment, &value);
+        match value {
+            Term::Map(m) => visitor.visit_map(MapDeserializer::new(m.map, self.options)),
+            _ => Err(Error::ExpectedMap),
+        }
+        .map_err(locate)
     }
 }
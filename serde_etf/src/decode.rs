@@ -0,0 +1,1160 @@
+use flate2::read::ZlibDecoder;
+use serde::de::value::SeqDeserializer;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::io::Read;
+use std::marker::PhantomData;
+use std::str;
+
+use crate::error::{Error, Limit, Result, Segment};
+use crate::options::{Limits, Options, DEFAULT};
+use crate::tag::*;
+
+/// Deserializer that reads the External Term Format directly from a byte
+/// slice, without building an intermediate [`eetf::Term`].
+///
+/// Binaries and atoms are borrowed from the input wherever possible, so
+/// `&'de str`, `&'de [u8]` and borrowed `Cow`s can be deserialized without
+/// allocating.
+///
+/// Compressed terms are decompressed transparently. Nothing can be borrowed
+/// from them, though.
+///
+/// The [`Limits`] of the options are enforced while decoding.
+pub struct Decoder<'de> {
+    input: &'de [u8],
+    // The decompressed term, if the input is compressed, and how much of it
+    // was read
+    decompressed: Option<(Vec<u8>, usize)>,
+    options: Options,
+    budget: Budget,
+}
+
+// What's left of the limits that apply while reading
+struct Budget {
+    depth: usize,
+    elements: usize,
+}
+
+// Reads terms from either the input of a `Decoder`, or its decompressed term.
+// `B` decides whether the data can be borrowed
+struct Reader<'b, 'o, B> {
+    input: &'b [u8],
+    options: &'o Options,
+    budget: &'o mut Budget,
+    borrow: PhantomData<B>,
+}
+
+// Whether data read from a `&'b [u8]` can be borrowed for `'de`
+trait Borrow<'de, 'b> {
+    fn borrow<T: ?Sized>(data: &'b T) -> Option<&'de T>;
+}
+
+struct Borrowed;
+
+struct Copied;
+
+impl<'de, 'b: 'de> Borrow<'de, 'b> for Borrowed {
+    fn borrow<T: ?Sized>(data: &'b T) -> Option<&'de T> {
+        Some(data)
+    }
+}
+
+impl<'de, 'b> Borrow<'de, 'b> for Copied {
+    fn borrow<T: ?Sized>(_data: &'b T) -> Option<&'de T> {
+        None
+    }
+}
+
+// A term, whose contents (if any) haven't been read yet
+enum Header<'b> {
+    Atom(Cow<'b, str>),
+    Integer(i32),
+    BigInteger { negative: bool, digits: &'b [u8] },
+    Float(f64),
+    Binary(&'b [u8]),
+    // A list of small integers, which Erlang uses for strings
+    ByteList(&'b [u8]),
+    List(u32),
+    Nil,
+    Tuple(u32),
+    Map(u32),
+    // Terms that can't be represented in Rust
+    Other,
+}
+
+impl<'de> Decoder<'de> {
+    /// Creates a decoder for a term, that is prefixed by the version byte.
+    pub fn from_slice(input: &'de [u8]) -> Result<Self> {
+        Decoder::from_slice_with(input, &Options::new())
+    }
+
+    pub fn from_slice_with(input: &'de [u8], options: &Options) -> Result<Self> {
+        let limits = &options.limits;
+        if input.len() > limits.max_size {
+            return Err(Error::LimitExceeded(Limit::Size));
+        }
+
+        let (input, decompressed) = match input {
+            [VERSION, COMPRESSED, rest @ ..] => (&[][..], Some((decompress(rest, limits)?, 0))),
+            [VERSION, rest @ ..] => (rest, None),
+            [] => return Err(Error::UnexpectedEof),
+            _ => return Err(Error::UnsupportedVersion),
+        };
+        Ok(Decoder {
+            input,
+            decompressed,
+            options: options.clone(),
+            budget: Budget {
+                depth: limits.max_depth,
+                elements: limits.max_elements,
+            },
+        })
+    }
+
+    /// Makes sure all input was consumed.
+    pub fn end(&self) -> Result<()> {
+        let remaining = match &self.decompressed {
+            Some((term, read)) => term.len() - read,
+            None => self.input.len(),
+        };
+        if remaining == 0 {
+            Ok(())
+        } else {
+            Err(Error::ExtraneousInput)
+        }
+    }
+}
+
+// A compressed term is made up of the size of the uncompressed term and the
+// zlib compressed term, without a version byte
+fn decompress(input: &[u8], limits: &Limits) -> Result<Vec<u8>> {
+    let (size, data) = input.split_first_chunk().ok_or(Error::UnexpectedEof)?;
+    let size = u32::from_be_bytes(*size);
+    // Including the version byte
+    if size as usize + 1 > limits.max_size {
+        return Err(Error::LimitExceeded(Limit::Size));
+    }
+
+    let mut decoder = ZlibDecoder::new(data);
+    let mut term = Vec::new();
+    // Never decompress more than the announced size
+    (&mut decoder)
+        .take(u64::from(size) + 1)
+        .read_to_end(&mut term)
+        .map_err(Error::Io)?;
+
+    if term.len() != size as usize {
+        return Err(Error::InvalidInput);
+    }
+    if decoder.total_in() != data.len() as u64 {
+        return Err(Error::ExtraneousInput);
+    }
+    Ok(term)
+}
+
+impl<'b, 'o, B> Reader<'b, 'o, B> {
+    fn new(input: &'b [u8], options: &'o Options, budget: &'o mut Budget) -> Self {
+        Reader {
+            input,
+            options,
+            budget,
+            borrow: PhantomData,
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'b [u8]> {
+        if self.input.len() < len {
+            return Err(Error::UnexpectedEof);
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_atom(&mut self, len: usize, utf8: bool) -> Result<Cow<'b, str>> {
+        let bytes = self.read_bytes(len)?;
+        if utf8 {
+            str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|_| Error::InvalidUtf8)
+        } else {
+            Ok(latin1(bytes))
+        }
+    }
+
+    fn read_header(&mut self) -> Result<Header<'b>> {
+        Ok(match self.read_u8()? {
+            ATOM_EXT => {
+                let len = self.read_u16()?;
+                Header::Atom(self.read_atom(len as usize, false)?)
+            }
+            SMALL_ATOM_EXT => {
+                let len = self.read_u8()?;
+                Header::Atom(self.read_atom(len as usize, false)?)
+            }
+            ATOM_UTF8_EXT => {
+                let len = self.read_u16()?;
+                Header::Atom(self.read_atom(len as usize, true)?)
+            }
+            SMALL_ATOM_UTF8_EXT => {
+                let len = self.read_u8()?;
+                Header::Atom(self.read_atom(len as usize, true)?)
+            }
+            SMALL_INTEGER_EXT => Header::Integer(i32::from(self.read_u8()?)),
+            INTEGER_EXT => Header::Integer(i32::from_be_bytes(self.read_array()?)),
+            SMALL_BIG_EXT => {
+                let len = self.read_u8()?;
+                self.read_big_integer(len as usize)?
+            }
+            LARGE_BIG_EXT => {
+                let len = self.read_u32()?;
+                self.read_big_integer(len as usize)?
+            }
+            NEW_FLOAT_EXT => Header::Float(f64::from_be_bytes(self.read_array()?)),
+            FLOAT_EXT => {
+                let bytes = self.read_bytes(31)?;
+                let value = str::from_utf8(bytes)
+                    .ok()
+                    .and_then(|s| s.trim_end_matches('\0').parse().ok())
+                    .ok_or(Error::ExpectedFloat)?;
+                Header::Float(value)
+            }
+            BINARY_EXT => {
+                let len = self.read_u32()?;
+                self.check_binary_size(len as usize)?;
+                Header::Binary(self.read_bytes(len as usize)?)
+            }
+            STRING_EXT => {
+                let len = self.read_u16()?;
+                self.check_binary_size(len as usize)?;
+                Header::ByteList(self.read_bytes(len as usize)?)
+            }
+            LIST_EXT => Header::List(self.read_u32()?),
+            NIL_EXT => Header::Nil,
+            SMALL_TUPLE_EXT => Header::Tuple(u32::from(self.read_u8()?)),
+            LARGE_TUPLE_EXT => Header::Tuple(self.read_u32()?),
+            MAP_EXT => Header::Map(self.read_u32()?),
+            _ => Header::Other,
+        })
+    }
+
+    fn read_big_integer(&mut self, len: usize) -> Result<Header<'b>> {
+        self.check_binary_size(len)?;
+        let negative = match self.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidInput),
+        };
+        let digits = self.read_bytes(len)?;
+        Ok(Header::BigInteger { negative, digits })
+    }
+
+    fn check_binary_size(&self, len: usize) -> Result<()> {
+        if len > self.options.limits.max_binary_size {
+            Err(Error::LimitExceeded(Limit::BinarySize))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Enters a list, tuple or map with `len` elements or entries
+    fn nested<T>(&mut self, len: u32, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.budget.elements = (self.budget.elements)
+            .checked_sub(len as usize)
+            .ok_or(Error::LimitExceeded(Limit::Elements))?;
+        self.budget.depth = (self.budget.depth)
+            .checked_sub(1)
+            .ok_or(Error::LimitExceeded(Limit::Depth))?;
+        let value = f(self);
+        self.budget.depth += 1;
+        value
+    }
+
+    // Read the term that ends a list
+    fn read_tail(&mut self) -> Result<()> {
+        match self.read_header()? {
+            Header::Nil => Ok(()),
+            _ => Err(Error::ImproperList),
+        }
+    }
+
+    fn visit_binary<'de, V>(&self, bytes: &'b [u8], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        match str::from_utf8(bytes) {
+            Ok(s) => match B::borrow(s) {
+                Some(s) => visitor.visit_borrowed_str(s),
+                None => visitor.visit_str(s),
+            },
+            Err(_) => match B::borrow(bytes) {
+                Some(bytes) => visitor.visit_borrowed_bytes(bytes),
+                None => visitor.visit_bytes(bytes),
+            },
+        }
+    }
+
+    fn visit_atom<'de, V>(&self, name: Cow<'b, str>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        match name {
+            Cow::Borrowed(name) => match B::borrow(name) {
+                Some(name) => visitor.visit_borrowed_str(name),
+                None => visitor.visit_str(name),
+            },
+            Cow::Owned(name) => visitor.visit_string(name),
+        }
+    }
+
+    fn visit_list<'de, V>(&mut self, len: u32, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        let value = self.visit_elements(len, visitor)?;
+        self.read_tail()?;
+        Ok(value)
+    }
+
+    // Byte lists are visited element by element, like any other list
+    fn visit_byte_list<'de, V>(&mut self, bytes: &'b [u8], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested(bytes.len() as u32, |_| {
+            let mut access = SeqDeserializer::new(bytes.iter().copied());
+            let value = visitor.visit_seq(&mut access)?;
+            access.end()?;
+            Ok(value)
+        })
+    }
+
+    fn visit_elements<'de, V>(&mut self, len: u32, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        self.nested(len, |de| {
+            let mut access = Access::new(de, len);
+            let value = visitor.visit_seq(&mut access)?;
+            access.end()?;
+            Ok(value)
+        })
+    }
+
+    fn visit_map<'de, V>(&mut self, len: u32, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        self.nested(len, |de| {
+            let mut access = Access::new(de, len);
+            let value = visitor.visit_map(&mut access)?;
+            access.end()?;
+            Ok(value)
+        })
+    }
+
+    fn deserialize_integer<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        match self.read_header()? {
+            Header::Integer(i) => visitor.visit_i32(i),
+            Header::BigInteger { negative, digits } => {
+                visit_big_integer(negative, digits, false, visitor)
+            }
+            _ => Err(Error::ExpectedInt),
+        }
+    }
+
+    fn deserialize_integer128<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        B: Borrow<'de, 'b>,
+    {
+        match self.read_header()? {
+            Header::Integer(i) => visitor.visit_i32(i),
+            Header::BigInteger { negative, digits } => {
+                visit_big_integer(negative, digits, true, visitor)
+            }
+            _ => Err(Error::ExpectedInt),
+        }
+    }
+}
+
+// Names a map entry by its encoded key where possible
+pub(crate) fn key_segment(key: &[u8], index: usize) -> Segment {
+    let mut budget = Budget {
+        depth: 0,
+        elements: 0,
+    };
+    match Reader::<Copied>::new(key, &DEFAULT, &mut budget).read_header() {
+        Ok(Header::Atom(name)) => Segment::Field(name.into_owned()),
+        Ok(Header::Binary(bytes)) => match str::from_utf8(bytes) {
+            Ok(name) => Segment::Field(name.to_string()),
+            Err(_) => Segment::Index(index),
+        },
+        Ok(Header::Integer(i)) => Segment::Field(i.to_string()),
+        _ => Segment::Index(index),
+    }
+}
+
+// Latin-1 maps directly to the first 256 code points
+fn latin1(bytes: &[u8]) -> Cow<'_, str> {
+    if bytes.is_ascii() {
+        Cow::Borrowed(str::from_utf8(bytes).unwrap())
+    } else {
+        Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect())
+    }
+}
+
+/// Sort key of an encoded map key, see [`MapOrder::Sorted`]
+///
+/// [`MapOrder::Sorted`]: crate::MapOrder::Sorted
+#[derive(Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum KeyOrder<'a> {
+    Integer(i128),
+    Atom(Cow<'a, str>),
+    Binary(&'a [u8]),
+    Other(&'a [u8]),
+}
+
+pub(crate) fn key_order(key: &[u8]) -> KeyOrder<'_> {
+    let mut budget = Budget {
+        depth: 0,
+        elements: 0,
+    };
+    match Reader::<Borrowed>::new(key, &DEFAULT, &mut budget).read_header() {
+        Ok(Header::Atom(name)) => KeyOrder::Atom(name),
+        Ok(Header::Binary(bytes)) => KeyOrder::Binary(bytes),
+        Ok(Header::Integer(i)) => KeyOrder::Integer(i.into()),
+        Ok(Header::BigInteger { negative, digits }) => {
+            match visit_big_integer(negative, digits, true, IntegerVisitor) {
+                Ok(i) => KeyOrder::Integer(i),
+                Err(_) => KeyOrder::Other(key),
+            }
+        }
+        _ => KeyOrder::Other(key),
+    }
+}
+
+// Reads any integer, that fits into an i128
+struct IntegerVisitor;
+
+impl Visitor<'_> for IntegerVisitor {
+    type Value = i128;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an integer")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<i128, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<i128, E> {
+        Ok(v.into())
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> std::result::Result<i128, E> {
+        Ok(v)
+    }
+}
+
+// Big integers are visited as 64 bit integers where they fit, so the visitors
+// of smaller types report them as out of range. Only 128 bit integers, and
+// `deserialize_any`, get the full 128 bits
+fn visit_big_integer<'de, V>(
+    negative: bool,
+    digits: &[u8],
+    wide: bool,
+    visitor: V,
+) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    // Digits are little endian, so leading zeros are at the end
+    let len = digits.iter().rposition(|d| *d != 0).map_or(0, |i| i + 1);
+    if len > 16 || (len > 8 && !wide) {
+        return Err(if negative {
+            Error::NumberTooSmall
+        } else {
+            Error::NumberTooLarge
+        });
+    }
+    if len > 8 {
+        return visit_big_integer128(negative, &digits[..len], visitor);
+    }
+
+    let mut bytes = [0; 8];
+    bytes[..len].copy_from_slice(&digits[..len]);
+    let magnitude = u64::from_le_bytes(bytes);
+
+    if !negative {
+        if let Ok(num) = i64::try_from(magnitude) {
+            visitor.visit_i64(num)
+        } else {
+            visitor.visit_u64(magnitude)
+        }
+    } else if magnitude <= i64::MIN.unsigned_abs() {
+        visitor.visit_i64(0i64.wrapping_sub_unsigned(magnitude))
+    } else if wide {
+        visitor.visit_i128(-i128::from(magnitude))
+    } else {
+        // Number is negative, but doesn't fit into i64
+        Err(Error::NumberTooSmall)
+    }
+}
+
+fn visit_big_integer128<'de, V>(negative: bool, digits: &[u8], visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let mut bytes = [0; 16];
+    bytes[..digits.len()].copy_from_slice(digits);
+    let magnitude = u128::from_le_bytes(bytes);
+
+    if !negative {
+        if let Ok(num) = i128::try_from(magnitude) {
+            visitor.visit_i128(num)
+        } else {
+            visitor.visit_u128(magnitude)
+        }
+    } else if magnitude <= i128::MIN.unsigned_abs() {
+        visitor.visit_i128(0i128.wrapping_sub_unsigned(magnitude))
+    } else {
+        Err(Error::NumberTooSmall)
+    }
+}
+
+pub fn from_slice<'a, T>(input: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_slice_with(input, &Options::new())
+}
+
+pub fn from_slice_with<'a, T>(input: &'a [u8], options: &Options) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut decoder = Decoder::from_slice_with(input, options)?;
+    let value = T::deserialize(&mut decoder)?;
+    decoder.end()?;
+    Ok(value)
+}
+
+// Deserializes with a reader over the remaining input, which borrows from it
+// unless the input was decompressed
+macro_rules! forward_to_reader {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            match &mut self.decompressed {
+                Some((term, read)) => {
+                    let mut reader =
+                        Reader::<Copied>::new(&term[*read..], &self.options, &mut self.budget);
+                    let value = (&mut reader).$method($($arg,)* visitor);
+                    *read = term.len() - reader.input.len();
+                    value
+                }
+                None => {
+                    let mut reader =
+                        Reader::<Borrowed>::new(self.input, &self.options, &mut self.budget);
+                    let value = (&mut reader).$method($($arg,)* visitor);
+                    self.input = reader.input;
+                    value
+                }
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    forward_to_reader! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+impl<'de, 'b, B> de::Deserializer<'de> for &mut Reader<'b, '_, B>
+where
+    B: Borrow<'de, 'b>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            // Booleans and null are atoms, too
+            Header::Atom(a) => match &*a {
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                name if name == self.options.null_atom => visitor.visit_unit(),
+                _ => self.visit_atom(a, visitor),
+            },
+            Header::Integer(i) => visitor.visit_i32(i),
+            Header::BigInteger { negative, digits } => {
+                visit_big_integer(negative, digits, true, visitor)
+            }
+            Header::Float(f) => visitor.visit_f64(f),
+            Header::Binary(b) => self.visit_binary(b, visitor),
+            // Without a type to go by, byte lists are lists of integers
+            Header::ByteList(b) => self.visit_byte_list(b, visitor),
+            Header::List(len) => self.visit_list(len, visitor),
+            Header::Nil => self.visit_elements(0, visitor),
+            Header::Tuple(len) => self.visit_elements(len, visitor),
+            Header::Map(len) => self.visit_map(len, visitor),
+            Header::Other => Err(Error::InvalidInput),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Atom(a) => match &*a {
+                "false" => visitor.visit_bool(false),
+                "true" => visitor.visit_bool(true),
+                _ => Err(Error::ExpectedBool),
+            },
+            Header::Integer(i) if !self.options.bool_atoms => match i {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                _ => Err(Error::ExpectedBool),
+            },
+            _ => Err(Error::ExpectedBool),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer128(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer128(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Float(f) => visitor.visit_f64(f),
+            _ => Err(Error::ExpectedFloat),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Binary(b) => self.visit_binary(b, visitor),
+            Header::Atom(a) => self.visit_atom(a, visitor),
+            // Each byte is a code point, so they're Latin-1 like atoms
+            Header::ByteList(b) => self.visit_atom(latin1(b), visitor),
+            _ => Err(Error::ExpectedString),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Binary(b) | Header::ByteList(b) => match B::borrow(b) {
+                Some(b) => visitor.visit_borrowed_bytes(b),
+                None => visitor.visit_bytes(b),
+            },
+            _ => Err(Error::ExpectedBytes),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let input = self.input;
+        match self.read_header()? {
+            Header::Atom(a) if a == self.options.null_atom => visitor.visit_none(),
+            _ => {
+                // Rewind, so the value can be read in full
+                self.input = input;
+                visitor.visit_some(self)
+            }
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Atom(a) if a == self.options.null_atom => visitor.visit_unit(),
+            _ => Err(Error::ExpectedNil),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::List(len) => self.visit_list(len, visitor),
+            Header::Nil => self.visit_elements(0, visitor),
+            Header::ByteList(b) => self.visit_byte_list(b, visitor),
+            _ => Err(Error::ExpectedList),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Tuple(l) => {
+                if l as usize != len {
+                    return Err(Error::WrongTupleLength);
+                }
+                self.visit_elements(l, visitor)
+            }
+            _ => Err(Error::ExpectedTuple),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_header()? {
+            Header::Map(len) => self.visit_map(len, visitor),
+            _ => Err(Error::ExpectedMap),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let input = self.input;
+        match self.read_header()? {
+            // Unit variants are encoded by name
+            Header::Binary(_) | Header::Atom(_) => {
+                self.input = input;
+                visitor.visit_enum(Enum {
+                    de: self,
+                    tagged: false,
+                    variant: input,
+                })
+            }
+            // Other variants are encoded as a map with a single entry from the
+            // variant name to its data
+            Header::Map(1) => self.nested(1, |de| {
+                let variant = de.input;
+                visitor.visit_enum(Enum {
+                    de,
+                    tagged: true,
+                    variant,
+                })
+            }),
+            _ => Err(Error::ExpectedEnum),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+}
+
+// Elements of a list or tuple, or entries of a map
+struct Access<'a, 'b, 'o, B> {
+    de: &'a mut Reader<'b, 'o, B>,
+    len: u32,
+    remaining: u32,
+    // Where the key of the current entry starts
+    key: &'b [u8],
+}
+
+impl<'a, 'b, 'o, B> Access<'a, 'b, 'o, B> {
+    fn new(de: &'a mut Reader<'b, 'o, B>, len: u32) -> Self {
+        Access {
+            de,
+            len,
+            remaining: len,
+            key: &[],
+        }
+    }
+
+    // Index of the element or entry that is currently being read
+    fn index(&self) -> usize {
+        (self.len - self.remaining - 1) as usize
+    }
+
+    fn end(&self) -> Result<()> {
+        if self.remaining == 0 {
+            Ok(())
+        } else {
+            Err(Error::ExtraneousInput)
+        }
+    }
+}
+
+impl<'de, 'b, B> SeqAccess<'de> for Access<'_, 'b, '_, B>
+where
+    B: Borrow<'de, 'b>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let tag = self.de.input.first().copied();
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|e| e.at(Segment::Index(self.index()), tag))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining as usize)
+    }
+}
+
+impl<'de, 'b, B> MapAccess<'de> for Access<'_, 'b, '_, B>
+where
+    B: Borrow<'de, 'b>,
+{
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.key = self.de.input;
+        let tag = self.key.first().copied();
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|e| e.at(key_segment(self.key, self.index()), tag))
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let tag = self.de.input.first().copied();
+        seed.deserialize(&mut *self.de)
+            .map_err(|e| e.at(key_segment(self.key, self.index()), tag))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining as usize)
+    }
+}
+
+struct Enum<'a, 'b, 'o, B> {
+    de: &'a mut Reader<'b, 'o, B>,
+    // Whether the variant is followed by data
+    tagged: bool,
+    // Where the variant name starts
+    variant: &'b [u8],
+}
+
+// Locates an error in the data of a variant, which starts at `data`
+fn in_variant(error: Error, variant: &[u8], data: &[u8]) -> Error {
+    error.at(key_segment(variant, 0), data.first().copied())
+}
+
+impl<'de, 'b, B> de::EnumAccess<'de> for Enum<'_, 'b, '_, B>
+where
+    B: Borrow<'de, 'b>,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'b, B> VariantAccess<'de> for Enum<'_, 'b, '_, B>
+where
+    B: Borrow<'de, 'b>,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        if self.tagged {
+            Err(Error::ExtraneousInput)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if !self.tagged {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            ));
+        }
+        let (variant, data) = (self.variant, self.de.input);
+        seed.deserialize(self.de)
+            .map_err(|e| in_variant(e, variant, data))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.tagged {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            ));
+        }
+        let (variant, data) = (self.variant, self.de.input);
+        match self.de.read_header()? {
+            Header::List(len) => self.de.visit_list(len, visitor),
+            Header::Nil => self.de.visit_elements(0, visitor),
+            Header::Tuple(len) => self.de.visit_elements(len, visitor),
+            _ => Err(Error::ExpectedList),
+        }
+        .map_err(|e| in_variant(e, variant, data))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.tagged {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            ));
+        }
+        let (variant, data) = (self.variant, self.de.input);
+        match self.de.read_header()? {
+            Header::Map(len) => self.de.visit_map(len, visitor),
+            _ => Err(Error::ExpectedMap),
+        }
+        .map_err(|e| in_variant(e, variant, data))
+    }
+}
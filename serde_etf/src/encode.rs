@@ -0,0 +1,704 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{ser, Serialize};
+use std::io::Write;
+
+use crate::decode::{key_order, key_segment};
+use crate::error::{Error, Result, Segment};
+use crate::options::{KeyStyle, MapOrder, Options, SnowflakeStyle, SNOWFLAKE};
+use crate::tag::*;
+use crate::value::ATOM;
+
+/// Serializer that writes the External Term Format directly, without building
+/// an intermediate [`eetf::Term`].
+///
+/// The number of entries in a map or list is only known once all of them were
+/// written, so the output is assembled in memory and lengths are patched in
+/// afterwards.
+///
+/// Struct fields are written in declaration order and map entries in the
+/// order they're serialized in, unless [`MapOrder::Sorted`] is set, so the
+/// same value always encodes to the same bytes.
+pub struct Encoder {
+    output: Vec<u8>,
+    options: Options,
+}
+
+pub struct Compound<'a> {
+    encoder: &'a mut Encoder,
+    // Position of the container tag in the output
+    start: usize,
+    count: u32,
+    // Position of the current map key in the output
+    key: usize,
+    // Start and key end of every map entry, if they have to be sorted
+    entries: Vec<(usize, usize)>,
+    // Name of the variant, whose data this is
+    variant: Option<&'static str>,
+}
+
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::new();
+    value.serialize(&mut encoder)?;
+    Ok(encoder.into_inner())
+}
+
+pub fn to_vec_with<T>(value: &T, options: &Options) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut encoder = Encoder::with_options(options.clone());
+    value.serialize(&mut encoder)?;
+    Ok(encoder.into_inner())
+}
+
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_with(writer, value, &Options::new())
+}
+
+pub fn to_writer_with<W, T>(mut writer: W, value: &T, options: &Options) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    writer
+        .write_all(&to_vec_with(value, options)?)
+        .map_err(Error::Io)
+}
+
+// Returns the compressed term, if it's smaller than the uncompressed one
+fn compress(term: &[u8]) -> Option<Vec<u8>> {
+    let size = u32::try_from(term.len()).ok()?;
+
+    let mut output = vec![VERSION, COMPRESSED];
+    output.extend_from_slice(&size.to_be_bytes());
+
+    let mut encoder = ZlibEncoder::new(output, Compression::default());
+    // Writing to a `Vec` can't fail
+    encoder.write_all(term).ok()?;
+    let output = encoder.finish().ok()?;
+
+    // Compressed terms have four more bytes of header
+    (output.len() < term.len() + 1).then_some(output)
+}
+
+// Free function, so atoms can be written while the options are borrowed.
+// Names too long for an atom are written as binaries, like in `rewrite_atom`
+fn write_atom(output: &mut Vec<u8>, name: &str) {
+    let Ok(len) = u16::try_from(name.len()) else {
+        output.push(BINARY_EXT);
+        output.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        output.extend_from_slice(name.as_bytes());
+        return;
+    };
+
+    if name.is_ascii() {
+        output.push(ATOM_EXT);
+    } else {
+        output.push(ATOM_UTF8_EXT);
+    }
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(name.as_bytes());
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Encoder::with_options(Options::new())
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        Encoder {
+            output: vec![VERSION],
+            options,
+        }
+    }
+
+    /// Returns the encoded term, including the version byte.
+    ///
+    /// The term is compressed if it's larger than the configured threshold,
+    /// and compressing actually makes it smaller.
+    pub fn into_inner(self) -> Vec<u8> {
+        let term = &self.output[1..];
+        match self.options.compression_threshold {
+            Some(threshold) if term.len() > threshold => compress(term).unwrap_or(self.output),
+            _ => self.output,
+        }
+    }
+
+    fn write_atom(&mut self, name: &str) {
+        write_atom(&mut self.output, name);
+    }
+
+    fn write_key(&mut self, name: &str) {
+        match self.options.key_style {
+            KeyStyle::Atom => self.write_atom(name),
+            KeyStyle::Binary => self.write_binary(name.as_bytes()),
+        }
+    }
+
+    // The binary written at `start`, if that's the last term in the output
+    fn binary_at(&self, start: usize) -> Option<&[u8]> {
+        let rest = self.output.get(start..)?;
+        if rest.first() != Some(&BINARY_EXT) {
+            return None;
+        }
+        let len = u32::from_be_bytes(rest.get(1..5)?.try_into().unwrap()) as usize;
+        let bytes = rest.get(5..)?;
+        (bytes.len() == len).then_some(bytes)
+    }
+
+    // Map keys are serialized like any other value, so a string key has to be
+    // turned into an atom afterwards
+    fn rewrite_key(&mut self, start: usize) {
+        if self.options.key_style == KeyStyle::Atom {
+            self.rewrite_atom(start);
+        }
+    }
+
+    // Turns the binary at `start` into an atom, if it's a valid atom name
+    fn rewrite_atom(&mut self, start: usize) {
+        let Some(name) = self.binary_at(start) else {
+            return;
+        };
+        if name.len() > u16::MAX as usize {
+            return;
+        }
+        let tag = match std::str::from_utf8(name) {
+            Ok(name) if name.is_ascii() => ATOM_EXT,
+            Ok(_) => ATOM_UTF8_EXT,
+            Err(_) => return,
+        };
+        let len = name.len() as u16;
+
+        // The atom header is two bytes shorter than the binary header
+        self.output.copy_within(start + 5.., start + 3);
+        self.output.truncate(self.output.len() - 2);
+        self.output[start] = tag;
+        self.output[start + 1..start + 3].copy_from_slice(&len.to_be_bytes());
+    }
+
+    // Snowflakes are serialized as strings, so they have to be turned into
+    // integers afterwards
+    fn rewrite_snowflake(&mut self, start: usize) {
+        let id = self
+            .binary_at(start)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|s| s.parse::<u64>().ok());
+        if let Some(id) = id {
+            self.output.truncate(start);
+            match i32::try_from(id) {
+                Ok(id) => self.write_fix_integer(id),
+                Err(_) => self.write_big_integer(false, id.into()),
+            }
+        }
+    }
+
+    fn write_binary(&mut self, bytes: &[u8]) {
+        self.output.push(BINARY_EXT);
+        self.output
+            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.output.extend_from_slice(bytes);
+    }
+
+    // Integers use the narrowest tag that fits, like Discord does
+    fn write_fix_integer(&mut self, v: i32) {
+        if let Ok(v) = u8::try_from(v) {
+            self.output.push(SMALL_INTEGER_EXT);
+            self.output.push(v);
+        } else {
+            self.output.push(INTEGER_EXT);
+            self.output.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    fn write_big_integer(&mut self, negative: bool, magnitude: u128) {
+        let digits = magnitude.to_le_bytes();
+        // Strip leading zeros, but always keep at least one digit
+        let len = digits.iter().rposition(|d| *d != 0).unwrap_or(0) + 1;
+
+        self.output.push(SMALL_BIG_EXT);
+        self.output.push(len as u8);
+        self.output.push(negative as u8);
+        self.output.extend_from_slice(&digits[..len]);
+    }
+
+    fn write_float(&mut self, v: f64) -> Result<()> {
+        if !v.is_finite() {
+            return Err(Error::NonFiniteFloat);
+        }
+        self.output.push(NEW_FLOAT_EXT);
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn write_tuple_header(&mut self, len: usize) {
+        if let Ok(len) = u8::try_from(len) {
+            self.output.push(SMALL_TUPLE_EXT);
+            self.output.push(len);
+        } else {
+            self.output.push(LARGE_TUPLE_EXT);
+            self.output.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+
+    // Variants with data are encoded as a map with a single entry from the
+    // variant name to the data
+    fn write_variant_tag(&mut self, variant: &str) {
+        self.output.push(MAP_EXT);
+        self.output.extend_from_slice(&1u32.to_be_bytes());
+        self.write_key(variant);
+    }
+
+    // Start a list or map, whose length is filled in by `Compound`
+    fn begin(&mut self, tag: u8) -> Compound<'_> {
+        let start = self.output.len();
+        self.output.push(tag);
+        self.output.extend_from_slice(&0u32.to_be_bytes());
+        Compound {
+            encoder: self,
+            start,
+            count: 0,
+            key: 0,
+            entries: Vec::new(),
+            variant: None,
+        }
+    }
+
+    fn begin_variant(&mut self, variant: &'static str, tag: u8) -> Compound<'_> {
+        self.write_variant_tag(variant);
+        let mut compound = self.begin(tag);
+        compound.variant = Some(variant);
+        compound
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder::new()
+    }
+}
+
+impl Compound<'_> {
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.count += 1;
+        value
+            .serialize(&mut *self.encoder)
+            .map_err(|e| self.locate(e, Segment::Index(self.count as usize - 1)))
+    }
+
+    fn field<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.count += 1;
+        self.encoder.write_key(key);
+        value
+            .serialize(&mut *self.encoder)
+            .map_err(|e| self.locate(e, Segment::Field(key.to_string())))
+    }
+
+    fn locate(&self, error: Error, segment: Segment) -> Error {
+        let error = error.at(segment, None);
+        match self.variant {
+            Some(variant) => error.at(Segment::Field(variant.to_string()), None),
+            None => error,
+        }
+    }
+
+    fn patch_count(&mut self) {
+        let at = self.start + 1;
+        self.encoder.output[at..at + 4].copy_from_slice(&self.count.to_be_bytes());
+    }
+
+    fn end_list(mut self) -> Result<()> {
+        if self.count == 0 {
+            // Empty lists are just NIL
+            self.encoder.output.truncate(self.start);
+        } else {
+            self.patch_count();
+        }
+        self.encoder.output.push(NIL_EXT);
+        Ok(())
+    }
+
+    // Entries are sorted after they were written, by moving their encodings
+    fn sort_entries(&mut self) {
+        let Some(&(first, _)) = self.entries.first() else {
+            return;
+        };
+        let entries = self.encoder.output.split_off(first);
+        let starts = self.entries.iter().map(|(start, _)| start - first);
+        let ends = starts.clone().skip(1).chain([entries.len()]);
+        let mut sorted = (self.entries.iter())
+            .zip(ends)
+            .map(|(&(start, key), end)| {
+                let entry = &entries[start - first..end];
+                (key_order(&entry[..key - start]), entry)
+            })
+            .collect::<Vec<_>>();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (_, entry) in sorted {
+            self.encoder.output.extend_from_slice(entry);
+        }
+    }
+
+    fn end_map(mut self) -> Result<()> {
+        self.patch_count();
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Encoder {
+    type Ok = ();
+
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        if self.options.bool_atoms {
+            self.write_atom(if v { "true" } else { "false" });
+        } else {
+            self.write_fix_integer(i32::from(v));
+        }
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        match i32::try_from(v) {
+            Ok(v) => self.write_fix_integer(v),
+            Err(_) => self.write_big_integer(v < 0, v.unsigned_abs().into()),
+        }
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        match i32::try_from(v) {
+            Ok(v) => self.write_fix_integer(v),
+            Err(_) => self.write_big_integer(v < 0, v.unsigned_abs()),
+        }
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        match i32::try_from(v) {
+            Ok(v) => self.write_fix_integer(v),
+            Err(_) => self.write_big_integer(false, v.into()),
+        }
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        match i32::try_from(v) {
+            Ok(v) => self.write_fix_integer(v),
+            Err(_) => self.write_big_integer(false, v),
+        }
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.write_float(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write_float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_binary(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_binary(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        write_atom(&mut self.output, &self.options.null_atom);
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let start = self.output.len();
+        value.serialize(&mut *self)?;
+        if name == ATOM {
+            self.rewrite_atom(start);
+        }
+        if name == SNOWFLAKE && self.options.snowflake_style == SnowflakeStyle::Integer {
+            self.rewrite_snowflake(start);
+        }
+        Ok(())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.write_variant_tag(variant);
+        value
+            .serialize(self)
+            .map_err(|e| e.at(Segment::Field(variant.to_string()), None))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(self.begin(LIST_EXT))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        let start = self.output.len();
+        self.write_tuple_header(len);
+        Ok(Compound {
+            encoder: self,
+            start,
+            count: 0,
+            key: 0,
+            entries: Vec::new(),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(self.begin_variant(variant, LIST_EXT))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(self.begin(MAP_EXT))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(self.begin_variant(variant, MAP_EXT))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_list()
+    }
+}
+
+// Tuples have a fixed length, so their header is already complete
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_list()
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = self.encoder.output.len();
+        self.element(key)?;
+        self.encoder.rewrite_key(self.key);
+        if self.encoder.options.map_order == MapOrder::Sorted {
+            let end = self.encoder.output.len();
+            self.entries.push((self.key, end));
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self.encoder).map_err(|e| {
+            let key = &self.encoder.output[self.key..];
+            self.locate(e, key_segment(key, self.count as usize - 1))
+        })
+    }
+
+    fn end(mut self) -> Result<()> {
+        self.sort_entries();
+        self.end_map()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_map()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_map()
+    }
+}
@@ -1,10 +1,15 @@
 use std::fmt::{Debug, Display, Formatter};
 
+use eetf::Term;
+
+use crate::tag;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Custom(String),
+    Io(std::io::Error),
     NonFiniteFloat,
     SerializeKey,
     SerializeValue,
@@ -22,13 +27,44 @@ pub enum Error {
     ExpectedTuple,
     ExpectedMap,
     ExpectedEnum,
+    ExpectedNil,
+    ImproperList,
     WrongTupleLength,
+    UnexpectedEof,
+    UnsupportedVersion,
+    LimitExceeded(Limit),
+    /// An error inside a list, tuple, map or enum variant
+    At {
+        path: Vec<Segment>,
+        // Tag of the offending term, if it was being decoded
+        tag: Option<u8>,
+        error: Box<Error>,
+    },
+}
+
+/// A step along the path to where an error occurred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Segment {
+    /// Struct field, map key or enum variant
+    Field(String),
+    /// List or tuple element, or map entry without a printable key
+    Index(usize),
+}
+
+/// The decoding limit that was exceeded, see [`Limits`](crate::Limits).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    Depth,
+    Elements,
+    BinarySize,
+    Size,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Custom(s) => write!(f, "{}", s),
+            Error::Io(e) => write!(f, "IO error: {}", e),
             Error::NonFiniteFloat => write!(f, "Tried to encode a non-finite float"),
             Error::SerializeKey => write!(
                 f,
@@ -54,7 +90,100 @@ impl Display for Error {
             Error::ExpectedTuple => write!(f, "Expected tuple"),
             Error::ExpectedMap => write!(f, "Expected map"),
             Error::ExpectedEnum => write!(f, "Expected enum"),
+            Error::ExpectedNil => write!(f, "Expected nil"),
+            Error::ImproperList => write!(f, "Improper lists can't be represented in Rust"),
             Error::WrongTupleLength => write!(f, "Found tuple with wrong length"),
+            Error::UnexpectedEof => write!(f, "Unexpected end of input"),
+            Error::UnsupportedVersion => write!(f, "Unsupported external term format version"),
+            Error::LimitExceeded(limit) => write!(f, "Input exceeds the {} limit", limit),
+            Error::At { path, tag, error } => {
+                write!(f, "{} at ", error)?;
+                for (i, segment) in path.iter().enumerate() {
+                    match segment {
+                        Segment::Field(name) if i == 0 => write!(f, "{}", name)?,
+                        Segment::Field(name) => write!(f, ".{}", name)?,
+                        Segment::Index(index) => write!(f, "[{}]", index)?,
+                    }
+                }
+                match tag.map(|t| (t, tag::name(t))) {
+                    Some((_, Some(name))) => write!(f, " ({})", name),
+                    Some((t, None)) => write!(f, " (tag {})", t),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Depth => write!(f, "depth"),
+            Limit::Elements => write!(f, "element"),
+            Limit::BinarySize => write!(f, "binary size"),
+            Limit::Size => write!(f, "size"),
+        }
+    }
+}
+
+impl Segment {
+    // Names map entries by their key where possible
+    pub(crate) fn key(key: &Term, index: usize) -> Segment {
+        match key {
+            Term::Atom(a) => Segment::Field(a.name.clone()),
+            Term::Binary(b) => match std::str::from_utf8(&b.bytes) {
+                Ok(name) => Segment::Field(name.to_string()),
+                Err(_) => Segment::Index(index),
+            },
+            Term::FixInteger(i) => Segment::Field(i.value.to_string()),
+            _ => Segment::Index(index),
+        }
+    }
+}
+
+impl Error {
+    /// The error without the location it occurred at.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::At { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Path to where the error occurred, empty if it occurred at the top
+    /// level.
+    pub fn path(&self) -> &[Segment] {
+        match self {
+            Error::At { path, .. } => path,
+            _ => &[],
+        }
+    }
+
+    /// Tag of the term that couldn't be decoded, if known.
+    pub fn tag(&self) -> Option<u8> {
+        match self {
+            Error::At { tag, .. } => *tag,
+            _ => None,
+        }
+    }
+
+    // Locates the error inside the container it propagates out of. The tag is
+    // only recorded for the innermost term
+    pub(crate) fn at(self, segment: Segment, tag: Option<u8>) -> Error {
+        match self {
+            Error::At {
+                mut path,
+                tag,
+                error,
+            } => {
+                path.insert(0, segment);
+                Error::At { path, tag, error }
+            }
+            error => Error::At {
+                path: vec![segment],
+                tag,
+                error: Box::new(error),
+            },
         }
     }
 }
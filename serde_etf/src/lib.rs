@@ -1,7 +1,16 @@
 mod de;
+mod decode;
+mod encode;
 mod error;
+mod options;
 mod ser;
+mod tag;
+mod value;
 
-pub use de::{from_term, Deserializer};
-pub use error::{Error, Result};
-pub use ser::{to_term, Serializer};
+pub use de::{from_term, from_term_with, Deserializer};
+pub use decode::{from_slice, from_slice_with, Decoder};
+pub use encode::{to_vec, to_vec_with, to_writer, to_writer_with, Encoder};
+pub use error::{Error, Limit, Result, Segment};
+pub use options::{KeyStyle, Limits, MapOrder, Options, SnowflakeStyle};
+pub use ser::{to_term, to_term_with, Serializer};
+pub use value::Value;
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+
+/// How struct fields, map keys and enum variant tags are encoded.
+///
+/// Decoding always accepts both.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum KeyStyle {
+    /// `#{op => 0}`
+    Atom,
+    /// `#{<<"op">> => 0}`
+    #[default]
+    Binary,
+}
+
+/// How snowflakes, i.e. twilight's `Id`s, are encoded.
+///
+/// Decoding always accepts both.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SnowflakeStyle {
+    /// As a (big) integer, like Discord does
+    Integer,
+    /// As a binary of the decimal representation, like in JSON
+    #[default]
+    String,
+}
+
+/// The order map entries are encoded in.
+///
+/// Struct fields are always encoded in declaration order. Only the byte
+/// encoder, [`to_vec_with`](crate::to_vec_with) and
+/// [`to_writer_with`](crate::to_writer_with), respects it.
+/// [`to_term_with`](crate::to_term_with) ignores it, since [`eetf::Map`]s are
+/// unordered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MapOrder {
+    /// In the order they're serialized in, which for a `HashMap` is random
+    #[default]
+    Insertion,
+    /// Sorted by key, so the output is the same for equal maps. Integers come
+    /// before atoms and atoms before binaries, each sorted by value. Any other
+    /// keys come last, sorted by their encoding
+    Sorted,
+}
+
+/// Bounds on what the byte decoder accepts, to decode untrusted input safely.
+///
+/// Exceeding any of them fails with [`Error::LimitExceeded`]. Only the maximum
+/// depth is limited by default, so deeply nested input can't overflow the
+/// stack.
+///
+/// [`Error::LimitExceeded`]: crate::Error::LimitExceeded
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    pub(crate) max_depth: usize,
+    pub(crate) max_elements: usize,
+    pub(crate) max_binary_size: usize,
+    pub(crate) max_size: usize,
+}
+
+impl Limits {
+    pub const fn new() -> Self {
+        Limits {
+            max_depth: 128,
+            max_elements: usize::MAX,
+            max_binary_size: usize::MAX,
+            max_size: usize::MAX,
+        }
+    }
+
+    /// How deeply lists, tuples, maps and enum variants may be nested.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// How many list and tuple elements and map entries the whole term may
+    /// contain.
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+
+    /// How large a single binary or big integer may be, in bytes.
+    pub fn max_binary_size(mut self, max_binary_size: usize) -> Self {
+        self.max_binary_size = max_binary_size;
+        self
+    }
+
+    /// How large the encoded term may be, in bytes. For compressed terms this
+    /// applies to both the compressed and the decompressed size.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::new()
+    }
+}
+
+// Name of the newtype struct that twilight-model serializes `Id`s as
+pub(crate) const SNOWFLAKE: &str = "Id";
+
+pub(crate) static DEFAULT: Options = Options::new();
+
+/// Configures how values are encoded and decoded, so the output can match what
+/// a specific client expects.
+///
+/// ```
+/// use serde_etf::{KeyStyle, Options, SnowflakeStyle};
+///
+/// let options = Options::new()
+///     .key_style(KeyStyle::Atom)
+///     .snowflake_style(SnowflakeStyle::Integer);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub(crate) key_style: KeyStyle,
+    pub(crate) null_atom: Cow<'static, str>,
+    pub(crate) bool_atoms: bool,
+    pub(crate) snowflake_style: SnowflakeStyle,
+    pub(crate) compression_threshold: Option<usize>,
+    pub(crate) map_order: MapOrder,
+    pub(crate) limits: Limits,
+}
+
+impl Options {
+    /// Binary keys, `nil` for null, booleans as atoms, snowflakes as strings
+    /// no compression, map entries in insertion order and the default
+    /// [`Limits`].
+    pub const fn new() -> Self {
+        Options {
+            key_style: KeyStyle::Binary,
+            null_atom: Cow::Borrowed("nil"),
+            bool_atoms: true,
+            snowflake_style: SnowflakeStyle::String,
+            compression_threshold: None,
+            map_order: MapOrder::Insertion,
+            limits: Limits::new(),
+        }
+    }
+
+    pub fn key_style(mut self, key_style: KeyStyle) -> Self {
+        self.key_style = key_style;
+        self
+    }
+
+    /// Name of the atom that `None` and `()` are encoded as.
+    pub fn null_atom(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.null_atom = name.into();
+        self
+    }
+
+    /// Whether booleans are encoded as the atoms `true` and `false`, or as the
+    /// integers `1` and `0`.
+    pub fn bool_atoms(mut self, bool_atoms: bool) -> Self {
+        self.bool_atoms = bool_atoms;
+        self
+    }
+
+    pub fn snowflake_style(mut self, snowflake_style: SnowflakeStyle) -> Self {
+        self.snowflake_style = snowflake_style;
+        self
+    }
+
+    /// Compress encoded terms, that are larger than `threshold` bytes.
+    ///
+    /// Only applies to the byte encoder, since [`eetf::Term`]s can't be
+    /// compressed.
+    pub fn compress_above(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// How map entries are ordered by the byte encoder, see [`MapOrder`].
+    pub fn map_order(mut self, map_order: MapOrder) -> Self {
+        self.map_order = map_order;
+        self
+    }
+
+    /// Limits for decoding untrusted input.
+    ///
+    /// Only apply to the byte decoder, since [`eetf::Term`]s have already been
+    /// decoded.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
+}
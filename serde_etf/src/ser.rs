@@ -1,42 +1,129 @@
-use std::collections::HashMap;
 use eetf::{Atom, BigInteger, Binary, FixInteger, Float, List, Map, Term, Tuple};
+use num_bigint::BigInt;
 use serde::{ser, Serialize};
+use std::collections::HashMap;
+use std::str;
 
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, Segment};
+use crate::options::{KeyStyle, Options, SnowflakeStyle, DEFAULT, SNOWFLAKE};
+use crate::value::ATOM;
+
+pub struct Serializer<'a> {
+    options: &'a Options,
+}
+
+pub struct SeqSerializer<'a> {
+    options: &'a Options,
+    elements: Vec<Term>,
+}
 
-pub struct Serializer {}
+pub struct MapSerializer<'a> {
+    options: &'a Options,
+    map: HashMap<Term, Term>,
+}
 
-pub struct SeqSerializer {
+pub struct TupleVariantSerializer<'a> {
+    options: &'a Options,
+    variant: &'static str,
     elements: Vec<Term>,
 }
 
-pub struct MapSerializer {
-    map: HashMap<Term, Term>
+pub struct StructVariantSerializer<'a> {
+    options: &'a Options,
+    variant: &'static str,
+    map: HashMap<Term, Term>,
 }
 
+impl<'a> Serializer<'a> {
+    pub fn with_options(options: &'a Options) -> Self {
+        Serializer { options }
+    }
+
+    fn key(&self, name: &str) -> Term {
+        match self.options.key_style {
+            KeyStyle::Atom => Term::Atom(Atom::from(name)),
+            KeyStyle::Binary => Term::Binary(Binary::from(name.as_bytes())),
+        }
+    }
+
+    // Map keys are serialized like any other value, so string keys have to be
+    // turned into atoms afterwards
+    fn map_key<T>(&mut self, key: &T) -> Result<Term>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = key.serialize(&mut *self)?;
+        Ok(match key {
+            Term::Binary(b) if self.options.key_style == KeyStyle::Atom => {
+                match String::from_utf8(b.bytes) {
+                    Ok(name) => Term::Atom(Atom::from(name)),
+                    Err(e) => Term::Binary(Binary::from(e.into_bytes())),
+                }
+            }
+            key => key,
+        })
+    }
+
+    // Serializes the value of an element, field or entry, so errors are
+    // located at `segment`
+    fn serialize_at<T>(&mut self, value: &T, segment: impl FnOnce() -> Segment) -> Result<Term>
+    where
+        T: ?Sized + Serialize,
+    {
+        value
+            .serialize(&mut *self)
+            .map_err(|e| e.at(segment(), None))
+    }
+
+    // Enum variants with data are externally tagged like in serde_json, i.e.
+    // they are encoded as a map with a single entry from the variant name to
+    // the data
+    fn tag_variant(&self, variant: &'static str, value: Term) -> Term {
+        let mut map = HashMap::with_capacity(1);
+        map.insert(self.key(variant), value);
+        Term::Map(Map::from(map))
+    }
+}
+
+/// Serializes a value into an [`eetf::Term`].
+///
+/// Maps become [`eetf::Map`]s, which don't keep their entries in any order.
+/// Use [`to_vec`](crate::to_vec) if the output has to be deterministic.
 pub fn to_term<T>(value: &T) -> Result<Term>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer {};
-    value.serialize(&mut serializer)
+    to_term_with(value, &DEFAULT)
+}
+
+/// Like [`to_term`], but with `options`. [`MapOrder`](crate::MapOrder) doesn't
+/// apply, use [`to_vec_with`](crate::to_vec_with) for sorted maps.
+pub fn to_term_with<T>(value: &T, options: &Options) -> Result<Term>
+where
+    T: Serialize,
+{
+    value.serialize(&mut Serializer::with_options(options))
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl<'a> ser::Serializer for &mut Serializer<'a> {
     type Ok = Term;
 
     type Error = Error;
 
-    type SerializeSeq = SeqSerializer;
-    type SerializeTuple = SeqSerializer;
-    type SerializeTupleStruct = SeqSerializer;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = MapSerializer;
-    type SerializeStruct = MapSerializer;
-    type SerializeStructVariant = Self;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = TupleVariantSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = StructVariantSerializer<'a>;
 
     fn serialize_bool(self, v: bool) -> Result<Term> {
-        Ok(Term::Atom(Atom::from(if v { "true" } else { "false" })))
+        if self.options.bool_atoms {
+            Ok(Term::Atom(Atom::from(if v { "true" } else { "false" })))
+        } else {
+            self.serialize_u8(u8::from(v))
+        }
     }
 
     fn serialize_i8(self, v: i8) -> Result<Term> {
@@ -48,19 +135,34 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_i32(self, v: i32) -> Result<Term> {
-        Ok(Term::FixInteger(FixInteger::from(v)))
+        self.serialize_i64(i64::from(v))
     }
 
+    // Integers use the narrowest tag that fits, like Discord does. FixIntegers
+    // are encoded as SMALL_INTEGER_EXT if they fit into a byte, otherwise
+    // as INTEGER_EXT
     fn serialize_i64(self, v: i64) -> Result<Term> {
-        Ok(Term::BigInteger(BigInteger::from(v)))
+        Ok(match i32::try_from(v) {
+            Ok(v) => Term::FixInteger(FixInteger::from(v)),
+            Err(_) => Term::BigInteger(BigInteger::from(v)),
+        })
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Term> {
+        Ok(match i32::try_from(v) {
+            Ok(v) => Term::FixInteger(FixInteger::from(v)),
+            Err(_) => Term::BigInteger(BigInteger {
+                value: BigInt::from(v),
+            }),
+        })
     }
 
     fn serialize_u8(self, v: u8) -> Result<Term> {
-        self.serialize_i16(i16::from(v))
+        self.serialize_u64(u64::from(v))
     }
 
     fn serialize_u16(self, v: u16) -> Result<Term> {
-        Ok(Term::FixInteger(FixInteger::from(v)))
+        self.serialize_u64(u64::from(v))
     }
 
     fn serialize_u32(self, v: u32) -> Result<Term> {
@@ -68,7 +170,19 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Term> {
-        Ok(Term::BigInteger(BigInteger::from(v)))
+        Ok(match i32::try_from(v) {
+            Ok(v) => Term::FixInteger(FixInteger::from(v)),
+            Err(_) => Term::BigInteger(BigInteger::from(v)),
+        })
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Term> {
+        Ok(match i32::try_from(v) {
+            Ok(v) => Term::FixInteger(FixInteger::from(v)),
+            Err(_) => Term::BigInteger(BigInteger {
+                value: BigInt::from(v),
+            }),
+        })
     }
 
     fn serialize_f32(self, v: f32) -> Result<Term> {
@@ -98,7 +212,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_none(self) -> Result<Term> {
-        Ok(Term::Atom(Atom::from("nil")))
+        Ok(Term::Atom(Atom::from(&*self.options.null_atom)))
     }
 
     fn serialize_some<T>(self, value: &T) -> Result<Term>
@@ -119,43 +233,60 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        variant_index: u32,
-        _variant: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
     ) -> Result<Term> {
-        self.serialize_u32(variant_index)
+        // Encode unit variants by name, like serde_json does, so ETF and JSON
+        // clients see the same values
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Term>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Term>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        let term = value.serialize(&mut *self)?;
+        if name == ATOM {
+            if let Term::Binary(b) = &term {
+                if let Ok(name) = str::from_utf8(&b.bytes) {
+                    return Ok(Term::Atom(Atom::from(name)));
+                }
+            }
+        }
+        if name == SNOWFLAKE && self.options.snowflake_style == SnowflakeStyle::Integer {
+            if let Term::Binary(b) = &term {
+                if let Some(id) = str::from_utf8(&b.bytes).ok().and_then(|s| s.parse().ok()) {
+                    return self.serialize_u64(id);
+                }
+            }
+        }
+        Ok(term)
     }
 
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
-        variant_index: u32,
-        _variant: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
         value: &T,
     ) -> Result<Term>
     where
         T: ?Sized + Serialize,
     {
-        Ok(Term::Tuple(Tuple::from(vec![
-            self.serialize_u32(variant_index)?,
-            value.serialize(self)?,
-        ])))
+        let value = self.serialize_at(value, || Segment::Field(variant.to_string()))?;
+        Ok(self.tag_variant(variant, value))
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         Ok(SeqSerializer {
-            elements: len.map(|l| Vec::with_capacity(l)).unwrap_or_default(),
+            options: self.options,
+            elements: len.map(Vec::with_capacity).unwrap_or_default(),
         })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
         Ok(SeqSerializer {
+            options: self.options,
             elements: Vec::with_capacity(len),
         })
     }
@@ -172,15 +303,20 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        todo!()
+        Ok(TupleVariantSerializer {
+            options: self.options,
+            variant,
+            elements: Vec::with_capacity(len),
+        })
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
         Ok(MapSerializer {
-            map: len.map(|l| HashMap::with_capacity(l)).unwrap_or_default(),
+            options: self.options,
+            map: len.map(HashMap::with_capacity).unwrap_or_default(),
         })
     }
 
@@ -192,14 +328,18 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        todo!()
+        Ok(StructVariantSerializer {
+            options: self.options,
+            variant,
+            map: HashMap::with_capacity(len),
+        })
     }
 }
 
-impl ser::SerializeSeq for SeqSerializer {
+impl ser::SerializeSeq for SeqSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
@@ -207,7 +347,10 @@ impl ser::SerializeSeq for SeqSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.elements.push(value.serialize(&mut Serializer {})?);
+        let index = self.elements.len();
+        let mut serializer = Serializer::with_options(self.options);
+        self.elements
+            .push(serializer.serialize_at(value, || Segment::Index(index))?);
         Ok(())
     }
 
@@ -217,7 +360,7 @@ impl ser::SerializeSeq for SeqSerializer {
     }
 }
 
-impl ser::SerializeTuple for SeqSerializer {
+impl ser::SerializeTuple for SeqSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
@@ -225,7 +368,10 @@ impl ser::SerializeTuple for SeqSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.elements.push(value.serialize(&mut Serializer {})?);
+        let index = self.elements.len();
+        let mut serializer = Serializer::with_options(self.options);
+        self.elements
+            .push(serializer.serialize_at(value, || Segment::Index(index))?);
         Ok(())
     }
 
@@ -234,7 +380,7 @@ impl ser::SerializeTuple for SeqSerializer {
     }
 }
 
-impl ser::SerializeTupleStruct for SeqSerializer {
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
@@ -242,7 +388,10 @@ impl ser::SerializeTupleStruct for SeqSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.elements.push(value.serialize(&mut Serializer {})?);
+        let index = self.elements.len();
+        let mut serializer = Serializer::with_options(self.options);
+        self.elements
+            .push(serializer.serialize_at(value, || Segment::Index(index))?);
         Ok(())
     }
 
@@ -251,23 +400,30 @@ impl ser::SerializeTupleStruct for SeqSerializer {
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for TupleVariantSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        let index = self.elements.len();
+        let mut serializer = Serializer::with_options(self.options);
+        let value = serializer
+            .serialize_at(value, || Segment::Index(index))
+            .map_err(|e| e.at(Segment::Field(self.variant.to_string()), None))?;
+        self.elements.push(value);
+        Ok(())
     }
 
     fn end(self) -> Result<Term> {
-        todo!()
+        let serializer = Serializer::with_options(self.options);
+        Ok(serializer.tag_variant(self.variant, Term::List(List::from(self.elements))))
     }
 }
 
-impl ser::SerializeMap for MapSerializer {
+impl ser::SerializeMap for MapSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
@@ -285,17 +441,18 @@ impl ser::SerializeMap for MapSerializer {
         Err(Error::SerializeValue)
     }
 
-    fn serialize_entry<K: ?Sized, V: ?Sized>(
-        &mut self,
-        key: &K,
-        value: &V,
-    ) -> std::result::Result<(), Self::Error>
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<()>
     where
-        K: Serialize,
-        V: Serialize,
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
     {
-        let mut serializer = Serializer{};
-        self.map.insert(key.serialize(&mut serializer)?, value.serialize(&mut serializer)?);
+        let index = self.map.len();
+        let mut serializer = Serializer::with_options(self.options);
+        let key = serializer
+            .map_key(key)
+            .map_err(|e| e.at(Segment::Index(index), None))?;
+        let value = serializer.serialize_at(value, || Segment::key(&key, index))?;
+        self.map.insert(key, value);
         Ok(())
     }
 
@@ -304,7 +461,7 @@ impl ser::SerializeMap for MapSerializer {
     }
 }
 
-impl ser::SerializeStruct for MapSerializer {
+impl ser::SerializeStruct for MapSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
@@ -312,8 +469,9 @@ impl ser::SerializeStruct for MapSerializer {
     where
         T: ?Sized + Serialize,
     {
-        let mut serializer = Serializer{};
-        self.map.insert(key.serialize(&mut serializer)?, value.serialize(&mut serializer)?);
+        let mut serializer = Serializer::with_options(self.options);
+        let value = serializer.serialize_at(value, || Segment::Field(key.to_string()))?;
+        self.map.insert(serializer.key(key), value);
         Ok(())
     }
 
@@ -322,19 +480,25 @@ impl ser::SerializeStruct for MapSerializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for StructVariantSerializer<'_> {
     type Ok = Term;
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        let mut serializer = Serializer::with_options(self.options);
+        let value = serializer
+            .serialize_at(value, || Segment::Field(key.to_string()))
+            .map_err(|e| e.at(Segment::Field(self.variant.to_string()), None))?;
+        self.map.insert(serializer.key(key), value);
+        Ok(())
     }
 
     fn end(self) -> Result<Term> {
-        todo!()
+        let serializer = Serializer::with_options(self.options);
+        Ok(serializer.tag_variant(self.variant, Term::Map(Map::from(self.map))))
     }
 }
 
@@ -0,0 +1,88 @@
+// Tags of the External Term Format, see
+// https://www.erlang.org/doc/apps/erts/erl_ext_dist.html
+
+use eetf::Term;
+
+pub const VERSION: u8 = 131;
+
+pub const NEW_FLOAT_EXT: u8 = 70;
+pub const COMPRESSED: u8 = 80;
+pub const SMALL_INTEGER_EXT: u8 = 97;
+pub const INTEGER_EXT: u8 = 98;
+pub const FLOAT_EXT: u8 = 99;
+pub const ATOM_EXT: u8 = 100;
+pub const SMALL_TUPLE_EXT: u8 = 104;
+pub const LARGE_TUPLE_EXT: u8 = 105;
+pub const NIL_EXT: u8 = 106;
+pub const STRING_EXT: u8 = 107;
+pub const LIST_EXT: u8 = 108;
+pub const BINARY_EXT: u8 = 109;
+pub const SMALL_BIG_EXT: u8 = 110;
+pub const LARGE_BIG_EXT: u8 = 111;
+pub const SMALL_ATOM_EXT: u8 = 115;
+pub const MAP_EXT: u8 = 116;
+pub const ATOM_UTF8_EXT: u8 = 118;
+pub const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+// Tags of terms that can't be represented in Rust
+pub const BIT_BINARY_EXT: u8 = 77;
+pub const NEW_PID_EXT: u8 = 88;
+pub const NEW_PORT_EXT: u8 = 89;
+pub const NEWER_REFERENCE_EXT: u8 = 90;
+pub const NEW_FUN_EXT: u8 = 112;
+pub const EXPORT_EXT: u8 = 113;
+
+pub fn name(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        NEW_FLOAT_EXT => "NEW_FLOAT_EXT",
+        COMPRESSED => "COMPRESSED",
+        SMALL_INTEGER_EXT => "SMALL_INTEGER_EXT",
+        INTEGER_EXT => "INTEGER_EXT",
+        FLOAT_EXT => "FLOAT_EXT",
+        ATOM_EXT => "ATOM_EXT",
+        SMALL_TUPLE_EXT => "SMALL_TUPLE_EXT",
+        LARGE_TUPLE_EXT => "LARGE_TUPLE_EXT",
+        NIL_EXT => "NIL_EXT",
+        STRING_EXT => "STRING_EXT",
+        LIST_EXT => "LIST_EXT",
+        BINARY_EXT => "BINARY_EXT",
+        SMALL_BIG_EXT => "SMALL_BIG_EXT",
+        LARGE_BIG_EXT => "LARGE_BIG_EXT",
+        SMALL_ATOM_EXT => "SMALL_ATOM_EXT",
+        MAP_EXT => "MAP_EXT",
+        ATOM_UTF8_EXT => "ATOM_UTF8_EXT",
+        SMALL_ATOM_UTF8_EXT => "SMALL_ATOM_UTF8_EXT",
+        BIT_BINARY_EXT => "BIT_BINARY_EXT",
+        NEW_PID_EXT => "NEW_PID_EXT",
+        NEW_PORT_EXT => "NEW_PORT_EXT",
+        NEWER_REFERENCE_EXT => "NEWER_REFERENCE_EXT",
+        NEW_FUN_EXT => "NEW_FUN_EXT",
+        EXPORT_EXT => "EXPORT_EXT",
+        _ => return None,
+    })
+}
+
+// The tag a term is encoded with
+pub fn of(term: &Term) -> u8 {
+    match term {
+        Term::Atom(a) if a.name.len() < 256 => SMALL_ATOM_UTF8_EXT,
+        Term::Atom(_) => ATOM_UTF8_EXT,
+        Term::FixInteger(i) if (0..256).contains(&i.value) => SMALL_INTEGER_EXT,
+        Term::FixInteger(_) => INTEGER_EXT,
+        Term::BigInteger(_) => SMALL_BIG_EXT,
+        Term::Float(_) => NEW_FLOAT_EXT,
+        Term::Pid(_) => NEW_PID_EXT,
+        Term::Port(_) => NEW_PORT_EXT,
+        Term::Reference(_) => NEWER_REFERENCE_EXT,
+        Term::ExternalFun(_) => EXPORT_EXT,
+        Term::InternalFun(_) => NEW_FUN_EXT,
+        Term::Binary(_) => BINARY_EXT,
+        Term::BitBinary(_) => BIT_BINARY_EXT,
+        Term::ByteList(_) => STRING_EXT,
+        Term::List(l) if l.elements.is_empty() => NIL_EXT,
+        Term::List(_) | Term::ImproperList(_) => LIST_EXT,
+        Term::Tuple(t) if t.elements.len() < 256 => SMALL_TUPLE_EXT,
+        Term::Tuple(_) => LARGE_TUPLE_EXT,
+        Term::Map(_) => MAP_EXT,
+    }
+}
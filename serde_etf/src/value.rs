@@ -0,0 +1,445 @@
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeTuple};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+use std::ops::Index;
+
+// Name of the newtype struct that atoms are serialized as, so this crate's
+// serializers can tell them apart from strings
+pub(crate) const ATOM: &str = "$serde_etf::Atom";
+
+static NIL: Value = Value::Nil;
+
+/// Any value that can be represented in the External Term Format.
+///
+/// Decoding can't tell atoms apart from strings, or tuples from lists, so
+/// atoms other than booleans and nil are decoded as strings and tuples as
+/// lists.
+///
+/// ```
+/// use serde_etf::{etf, Value};
+///
+/// let payload = etf!(#{ "op" => 2, "d" => #{ "token" => "abc", "intents" => [1, 2] } });
+/// assert_eq!(payload["d"]["token"], Value::from("abc"));
+/// assert_eq!(payload["d"]["intents"][1], Value::from(2));
+/// assert_eq!(payload["t"], Value::Nil);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Value {
+    #[default]
+    Nil,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    Atom(String),
+    String(String),
+    /// A binary that isn't valid UTF-8
+    Binary(Vec<u8>),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// Entries in insertion order
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// Looks up a map entry by its string or atom key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The contents of a string or atom.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::Atom(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => i64::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Integer(i) => u64::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Value::Nil)
+    }
+}
+
+/// Missing keys and indices are `Nil`, like in `serde_json`.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.get(key).unwrap_or(&NIL)
+    }
+}
+
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Value::List(elements) | Value::Tuple(elements) => elements.get(index).unwrap_or(&NIL),
+            _ => &NIL,
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Nil => serializer.serialize_none(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => {
+                if let Ok(i) = i64::try_from(*i) {
+                    serializer.serialize_i64(i)
+                } else if let Ok(i) = u64::try_from(*i) {
+                    serializer.serialize_u64(i)
+                } else {
+                    serializer.serialize_i128(*i)
+                }
+            }
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::Atom(name) => serializer.serialize_newtype_struct(ATOM, name),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Binary(b) => serializer.serialize_bytes(b),
+            Value::List(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Tuple(elements) => {
+                let mut tuple = serializer.serialize_tuple(elements.len())?;
+                for element in elements {
+                    tuple.serialize_element(element)?;
+                }
+                tuple.end()
+            }
+            Value::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Binary(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Binary(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Value::List(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty)*) => {$(
+        impl From<$ty> for Value {
+            fn from(v: $ty) -> Self {
+                Value::Integer(v.into())
+            }
+        }
+    )*};
+}
+
+from_integer!(i8 i16 i32 i64 i128 u8 u16 u32 u64);
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Nil, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Self {
+        Value::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(v: serde_json::Value) -> Self {
+        match v {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    Value::Integer(i.into())
+                } else if let Some(u) = n.as_u64() {
+                    Value::Integer(u.into())
+                } else {
+                    Value::Float(n.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(a) => Value::List(a.into_iter().map(Value::from).collect()),
+            serde_json::Value::Object(o) => Value::Map(
+                o.into_iter()
+                    .map(|(k, v)| (Value::String(k), Value::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Atoms become strings, tuples become arrays, binaries become arrays of bytes
+/// and keys that aren't strings become their JSON representation. Integers
+/// outside of the 64-bit range and non-finite floats can't be represented and
+/// become `null`.
+impl From<Value> for serde_json::Value {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Nil => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Integer(i) => {
+                if let Ok(i) = i64::try_from(i) {
+                    i.into()
+                } else if let Ok(u) = u64::try_from(i) {
+                    u.into()
+                } else {
+                    serde_json::Value::Null
+                }
+            }
+            Value::Float(f) => serde_json::Number::from_f64(f)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::Atom(s) | Value::String(s) => serde_json::Value::String(s),
+            Value::Binary(b) => b.into(),
+            Value::List(elements) | Value::Tuple(elements) => {
+                serde_json::Value::Array(elements.into_iter().map(Into::into).collect())
+            }
+            Value::Map(entries) => serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| {
+                        let key = match k {
+                            Value::Atom(s) | Value::String(s) => s,
+                            k => serde_json::Value::from(k).to_string(),
+                        };
+                        (key, v.into())
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Builds a [`Value`] from an Erlang-like literal.
+///
+/// Lists are written `[a, b]`, tuples `{a, b}` and maps `#{k => v}`. Atoms are
+/// written `:name`, `nil`, `true` and `false` are literals and anything else is
+/// converted with `Value::from`.
+///
+/// ```
+/// use serde_etf::{etf, Value};
+///
+/// let id = 42u64;
+/// let value = etf!(#{ :op => 0, :d => { :ok, [id, nil] } });
+/// assert_eq!(value["op"], Value::from(0));
+/// assert_eq!(value["d"][0], Value::Atom("ok".to_string()));
+/// assert_eq!(value["d"][1][0], Value::from(42));
+/// ```
+#[macro_export]
+macro_rules! etf {
+    // Elements of lists and tuples are munched one token at a time, until the
+    // next comma
+    (@seq $kind:ident [$($done:tt)*] []) => {
+        $crate::Value::$kind(vec![$($done)*])
+    };
+    (@seq $kind:ident [$($done:tt)*] [$($element:tt)+]) => {
+        $crate::Value::$kind(vec![$($done)* $crate::etf!($($element)+)])
+    };
+    (@seq $kind:ident [$($done:tt)*] [$($element:tt)+] , $($rest:tt)*) => {
+        $crate::etf!(@seq $kind [$($done)* $crate::etf!($($element)+),] [] $($rest)*)
+    };
+    (@seq $kind:ident [$($done:tt)*] [$($element:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::etf!(@seq $kind [$($done)*] [$($element)* $next] $($rest)*)
+    };
+
+    // Map keys are munched until the next `=>`, values until the next comma
+    (@key [$($done:tt)*] []) => {
+        $crate::Value::Map(vec![$($done)*])
+    };
+    (@key [$($done:tt)*] [$($key:tt)+] => $($rest:tt)*) => {
+        $crate::etf!(@value [$($done)*] [$($key)+] [] $($rest)*)
+    };
+    (@key [$($done:tt)*] [$($key:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::etf!(@key [$($done)*] [$($key)* $next] $($rest)*)
+    };
+    (@value [$($done:tt)*] [$($key:tt)+] [$($value:tt)+]) => {
+        $crate::Value::Map(vec![$($done)* ($crate::etf!($($key)+), $crate::etf!($($value)+))])
+    };
+    (@value [$($done:tt)*] [$($key:tt)+] [$($value:tt)+] , $($rest:tt)*) => {
+        $crate::etf!(
+            @key [$($done)* ($crate::etf!($($key)+), $crate::etf!($($value)+)),] [] $($rest)*
+        )
+    };
+    (@value [$($done:tt)*] [$($key:tt)+] [$($value:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::etf!(@value [$($done)*] [$($key)+] [$($value)* $next] $($rest)*)
+    };
+
+    (nil) => {
+        $crate::Value::Nil
+    };
+    (true) => {
+        $crate::Value::Bool(true)
+    };
+    (false) => {
+        $crate::Value::Bool(false)
+    };
+    (: $atom:ident) => {
+        $crate::Value::Atom(stringify!($atom).to_string())
+    };
+    ([$($elements:tt)*]) => {
+        $crate::etf!(@seq List [] [] $($elements)*)
+    };
+    ({$($elements:tt)*}) => {
+        $crate::etf!(@seq Tuple [] [] $($elements)*)
+    };
+    (#{$($entries:tt)*}) => {
+        $crate::etf!(@key [] [] $($entries)*)
+    };
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_slice, to_vec};
+use std::borrow::Cow;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Borrowed<'a> {
+    name: &'a str,
+    #[serde(with = "bytes")]
+    data: &'a [u8],
+    #[serde(borrow)]
+    cow: Cow<'a, str>,
+    #[serde(borrow)]
+    list: Vec<&'a str>,
+    #[serde(borrow)]
+    maybe: Option<&'a str>,
+}
+
+// `&[u8]` deserializes from a byte array, but we want it to come from a binary
+mod bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(v)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'de [u8], D::Error> {
+        <&[u8]>::deserialize(deserializer)
+    }
+}
+
+fn contains<T: ?Sized>(input: &[u8], value: &T) -> bool {
+    let range = input.as_ptr_range();
+    let ptr = value as *const T as *const u8;
+    range.contains(&ptr)
+}
+
+#[test]
+fn borrows_from_input() {
+    let bytes = to_vec(&Borrowed {
+        name: "name",
+        data: &[0, 159, 146, 150],
+        cow: Cow::Borrowed("cow"),
+        list: vec!["a", "b"],
+        maybe: Some("maybe"),
+    })
+    .unwrap();
+
+    let value = from_slice::<Borrowed>(&bytes).unwrap();
+
+    assert_eq!(value.name, "name");
+    assert!(contains(&bytes, value.name));
+    assert_eq!(value.data, [0, 159, 146, 150]);
+    assert!(contains(&bytes, value.data));
+    assert!(matches!(value.cow, Cow::Borrowed("cow")));
+    assert!(value.list.iter().all(|s| contains(&bytes, *s)));
+    assert!(contains(&bytes, value.maybe.unwrap()));
+}
+
+#[test]
+fn borrows_identifiers_and_atoms() {
+    // #{name => 'atom'}, with the key and value as atoms
+    let bytes = [
+        131, 116, 0, 0, 0, 1, 119, 4, b'n', b'a', b'm', b'e', 119, 4, b'a', b't', b'o', b'm',
+    ];
+
+    #[derive(Deserialize)]
+    struct Atom<'a> {
+        name: &'a str,
+    }
+
+    let value = from_slice::<Atom>(&bytes).unwrap();
+    assert_eq!(value.name, "atom");
+    assert!(contains(&bytes, value.name));
+}
+
+#[test]
+fn latin1_atom_is_owned() {
+    // 'grün' as a Latin-1 ATOM_EXT
+    let bytes = [131, 100, 0, 4, b'g', b'r', 0xfc, b'n'];
+    assert!(from_slice::<&str>(&bytes).is_err());
+    assert!(matches!(
+        from_slice::<Cow<str>>(&bytes).unwrap(),
+        Cow::Owned(s) if s == "grün"
+    ));
+}
+
+#[test]
+fn invalid_utf8_is_not_a_str() {
+    let bytes = [131, 109, 0, 0, 0, 2, 0xff, 0xfe];
+    assert!(from_slice::<&str>(&bytes).is_err());
+    assert!(from_slice::<String>(&bytes).is_err());
+}
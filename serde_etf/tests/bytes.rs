@@ -0,0 +1,230 @@
+use eetf::Term;
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_slice, from_term, to_term, to_vec, to_writer, Error};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum Kind {
+    Plain,
+    Count(u32),
+    Pair(i32, String),
+    Named { id: u64, tags: Vec<String> },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Inner {
+    flag: bool,
+    ratio: f64,
+    maybe: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Outer {
+    small: u8,
+    medium: u16,
+    large: u32,
+    negative: i16,
+    int: i32,
+    big: i64,
+    huge: u64,
+    name: String,
+    unicode: String,
+    bytes: Vec<u8>,
+    numbers: Vec<u16>,
+    empty: Vec<Inner>,
+    inner: Inner,
+    nested: Vec<Inner>,
+    pair: (u16, String),
+    kinds: Vec<Kind>,
+    lookup: HashMap<String, i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+}
+
+fn sample() -> Outer {
+    Outer {
+        small: 255,
+        medium: 1000,
+        large: 4_000_000_000,
+        negative: -300,
+        int: -70000,
+        big: i64::MIN,
+        huge: u64::MAX,
+        name: "radiance".to_string(),
+        unicode: "grüße ✨".to_string(),
+        bytes: vec![0, 1, 2, 255],
+        numbers: vec![1, 2, 300],
+        empty: vec![],
+        inner: Inner {
+            flag: true,
+            ratio: 0.5,
+            maybe: Some(false),
+        },
+        nested: vec![
+            Inner {
+                flag: false,
+                ratio: -1.25,
+                maybe: None,
+            };
+            3
+        ],
+        pair: (7, "seven".to_string()),
+        kinds: vec![
+            Kind::Plain,
+            Kind::Count(3),
+            Kind::Pair(-1, "one".to_string()),
+            Kind::Named {
+                id: 1 << 40,
+                tags: vec!["a".to_string(), "b".to_string()],
+            },
+        ],
+        lookup: HashMap::from([("a".to_string(), 1), ("b".to_string(), -2)]),
+        skipped: None,
+    }
+}
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+fn encode(term: &Term) -> Vec<u8> {
+    let mut buf = Vec::new();
+    term.encode(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn to_vec_matches_to_term() {
+    let value = sample();
+    assert_eq!(decode(&to_vec(&value).unwrap()), to_term(&value).unwrap());
+}
+
+#[test]
+fn to_vec_matches_term_encoding() {
+    // Without maps, the output is identical down to the byte
+    let value = (
+        1u16,
+        -5i64,
+        "str",
+        vec![Some(true), None],
+        Kind::Plain,
+        Kind::Pair(1, "x".to_string()),
+    );
+    assert_eq!(to_vec(&value).unwrap(), encode(&to_term(&value).unwrap()));
+}
+
+#[test]
+fn to_writer_matches_to_vec() {
+    let value = sample();
+    let mut buf = Vec::new();
+    to_writer(&mut buf, &value).unwrap();
+    assert_eq!(buf, to_vec(&value).unwrap());
+}
+
+#[test]
+fn from_slice_round_trip() {
+    let value = sample();
+    assert_eq!(
+        from_slice::<Outer>(&to_vec(&value).unwrap()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn from_slice_matches_from_term() {
+    let value = sample();
+    let bytes = to_vec(&value).unwrap();
+    assert_eq!(
+        from_slice::<Outer>(&bytes).unwrap(),
+        from_term::<Outer>(decode(&bytes)).unwrap()
+    );
+}
+
+#[test]
+fn empty_list_is_nil() {
+    assert_eq!(to_vec(&Vec::<u32>::new()).unwrap(), [131, 106]);
+}
+
+#[test]
+fn unknown_length_sequence() {
+    struct Odd;
+
+    impl Serialize for Odd {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..10u16).filter(|i| i % 2 == 1))
+        }
+    }
+
+    assert_eq!(
+        from_slice::<Vec<u16>>(&to_vec(&Odd).unwrap()).unwrap(),
+        vec![1, 3, 5, 7, 9]
+    );
+}
+
+#[test]
+fn latin1_atom() {
+    // #{'grün' => 1} with the key as a Latin-1 ATOM_EXT
+    let bytes = [
+        131, 116, 0, 0, 0, 1, 100, 0, 4, b'g', b'r', 0xfc, b'n', 97, 1,
+    ];
+    #[derive(Deserialize)]
+    struct Green {
+        #[serde(rename = "grün")]
+        value: u8,
+    }
+
+    assert_eq!(from_slice::<Green>(&bytes).unwrap().value, 1);
+}
+
+#[test]
+fn old_float() {
+    let mut bytes = vec![131, 99];
+    let mut repr = format!("{:.20e}", 1.5f64).into_bytes();
+    repr.resize(31, 0);
+    bytes.extend(repr);
+    assert_eq!(from_slice::<f64>(&bytes).unwrap(), 1.5);
+}
+
+#[test]
+fn rejects_truncated_input() {
+    let bytes = to_vec(&sample()).unwrap();
+    for len in 1..bytes.len() {
+        assert!(from_slice::<Outer>(&bytes[..len]).is_err());
+    }
+    assert!(matches!(
+        from_slice::<String>(&bytes[..1]),
+        Err(Error::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn rejects_wrong_version() {
+    assert!(matches!(
+        from_slice::<u8>(&[130, 97, 1]),
+        Err(Error::UnsupportedVersion)
+    ));
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    assert!(matches!(
+        from_slice::<u8>(&[131, 97, 1, 97, 2]),
+        Err(Error::ExtraneousInput)
+    ));
+}
+
+#[test]
+fn rejects_improper_list() {
+    // [1 | 2]
+    let bytes = [131, 108, 0, 0, 0, 1, 97, 1, 97, 2];
+    assert!(matches!(
+        from_slice::<Vec<u8>>(&bytes),
+        Err(Error::ImproperList)
+    ));
+}
+
+#[test]
+fn rejects_non_finite_float() {
+    assert!(matches!(to_vec(&f64::NAN), Err(Error::NonFiniteFloat)));
+}
@@ -0,0 +1,155 @@
+use eetf::Term;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_slice, to_vec, to_vec_with, Decoder, Error, Options};
+use std::borrow::Cow;
+use std::io::{Cursor, Write};
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Member {
+    id: u64,
+    name: String,
+    roles: Vec<u64>,
+}
+
+fn members() -> Vec<Member> {
+    (0..100)
+        .map(|i| Member {
+            id: 1 << 40 | i,
+            name: format!("member {i}"),
+            roles: vec![1 << 41, 1 << 42],
+        })
+        .collect()
+}
+
+// Compresses an encoded term like `term_to_binary(Term, [compressed])`
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    let term = &bytes[1..];
+    let mut encoder = ZlibEncoder::new(vec![131, 80], Compression::default());
+    encoder
+        .get_mut()
+        .extend_from_slice(&(term.len() as u32).to_be_bytes());
+    encoder.write_all(term).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn decodes_compressed_terms() {
+    let bytes = compress(&to_vec(&members()).unwrap());
+    assert_eq!(from_slice::<Vec<Member>>(&bytes).unwrap(), members());
+}
+
+#[test]
+fn compressed_terms_are_owned() {
+    let bytes = compress(&to_vec(&"borrowed").unwrap());
+
+    assert_eq!(from_slice::<String>(&bytes).unwrap(), "borrowed");
+    assert!(matches!(
+        from_slice::<Cow<str>>(&bytes).unwrap(),
+        Cow::Owned(s) if s == "borrowed"
+    ));
+    assert!(from_slice::<&str>(&bytes).is_err());
+}
+
+#[test]
+fn decoder_reads_compressed_terms() {
+    let bytes = compress(&to_vec(&members()).unwrap());
+    let mut decoder = Decoder::from_slice(&bytes).unwrap();
+    let value = Vec::<Member>::deserialize(&mut decoder).unwrap();
+    decoder.end().unwrap();
+    assert_eq!(value, members());
+}
+
+#[test]
+fn compresses_above_threshold() {
+    let options = Options::new().compress_above(64);
+
+    let bytes = to_vec_with(&members(), &options).unwrap();
+    assert_eq!(bytes[..2], [131, 80]);
+    assert!(bytes.len() < to_vec(&members()).unwrap().len());
+    assert_eq!(from_slice::<Vec<Member>>(&bytes).unwrap(), members());
+    // eetf understands them, too
+    assert_eq!(
+        Term::decode(Cursor::new(&bytes)).unwrap(),
+        Term::decode(Cursor::new(to_vec(&members()).unwrap())).unwrap()
+    );
+
+    // Small terms stay uncompressed
+    assert_eq!(
+        to_vec_with(&"small", &options).unwrap(),
+        to_vec(&"small").unwrap()
+    );
+}
+
+#[test]
+fn skips_compression_if_larger() {
+    // Incompressible data
+    let noise = (0..200u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    let bytes = serde_bytes(&noise);
+
+    assert_eq!(
+        to_vec_with(&bytes, &Options::new().compress_above(0)).unwrap(),
+        to_vec(&bytes).unwrap()
+    );
+}
+
+fn serde_bytes(bytes: &[u8]) -> impl Serialize + '_ {
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    Bytes(bytes)
+}
+
+#[test]
+fn rejects_wrong_size() {
+    let mut bytes = compress(&to_vec(&members()).unwrap());
+    bytes[5] += 1;
+    assert!(matches!(
+        from_slice::<Vec<Member>>(&bytes),
+        Err(Error::InvalidInput)
+    ));
+}
+
+#[test]
+fn rejects_corrupt_data() {
+    let bytes = compress(&to_vec(&members()).unwrap());
+
+    assert!(matches!(
+        from_slice::<Vec<Member>>(&bytes[..bytes.len() / 2]),
+        Err(Error::Io(_))
+    ));
+    assert!(matches!(
+        from_slice::<Vec<Member>>(&bytes[..4]),
+        Err(Error::UnexpectedEof)
+    ));
+
+    let mut bytes = bytes;
+    bytes[10] ^= 0xff;
+    assert!(from_slice::<Vec<Member>>(&bytes).is_err());
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    // After the zlib stream
+    let mut bytes = compress(&to_vec(&1u8).unwrap());
+    bytes.push(0);
+    assert!(matches!(
+        from_slice::<u8>(&bytes),
+        Err(Error::ExtraneousInput)
+    ));
+
+    // In the decompressed term
+    let bytes = compress(&[131, 97, 1, 97, 2]);
+    assert!(matches!(
+        from_slice::<u8>(&bytes),
+        Err(Error::ExtraneousInput)
+    ));
+}
@@ -0,0 +1,177 @@
+use eetf::{Atom, Binary, FixInteger, List, Map, Term, Tuple};
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_term, to_term, Error, Segment};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+enum Unit {
+    First,
+    #[serde(rename = "second")]
+    Second,
+}
+
+fn binary(s: &str) -> Term {
+    Term::Binary(Binary::from(s.as_bytes()))
+}
+
+#[test]
+fn unit_variant_is_encoded_by_name() {
+    assert_eq!(to_term(&Unit::First).unwrap(), binary("First"));
+}
+
+#[test]
+fn unit_variant_honors_rename() {
+    assert_eq!(to_term(&Unit::Second).unwrap(), binary("second"));
+}
+
+#[test]
+fn unit_variant_round_trip() {
+    for variant in [Unit::First, Unit::Second] {
+        let term = to_term(&variant).unwrap();
+        assert_eq!(from_term::<Unit>(term).unwrap(), variant);
+    }
+}
+
+#[test]
+fn unit_variant_from_atom() {
+    let term = Term::Atom(Atom::from("second"));
+    assert_eq!(from_term::<Unit>(term).unwrap(), Unit::Second);
+}
+
+#[test]
+fn unit_variant_rejects_index() {
+    let term = Term::FixInteger(FixInteger::from(0));
+    assert!(matches!(from_term::<Unit>(term), Err(Error::ExpectedEnum)));
+}
+
+#[test]
+fn unit_variant_rejects_unknown_name() {
+    assert!(from_term::<Unit>(binary("third")).is_err());
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum Data {
+    Newtype(u16),
+    Tuple(u16, String),
+    Struct {
+        a: u16,
+        #[serde(rename = "bee")]
+        b: String,
+    },
+    #[serde(rename = "unit")]
+    Unit,
+}
+
+fn tagged(term: &Term) -> (&Term, &Term) {
+    let Term::Map(map) = term else {
+        panic!("{term:?} isn't a map");
+    };
+    assert_eq!(map.map.len(), 1);
+    map.map.iter().next().unwrap()
+}
+
+#[test]
+fn newtype_variant_is_tagged() {
+    let term = to_term(&Data::Newtype(7)).unwrap();
+    let (variant, value) = tagged(&term);
+    assert_eq!(variant, &binary("Newtype"));
+    assert_eq!(value, &Term::FixInteger(FixInteger::from(7)));
+}
+
+#[test]
+fn tuple_variant_is_tagged() {
+    let term = to_term(&Data::Tuple(7, "x".to_string())).unwrap();
+    let (variant, value) = tagged(&term);
+    assert_eq!(variant, &binary("Tuple"));
+    assert_eq!(
+        value,
+        &Term::List(List::from(vec![
+            Term::FixInteger(FixInteger::from(7)),
+            binary("x"),
+        ]))
+    );
+}
+
+#[test]
+fn struct_variant_is_tagged() {
+    let term = to_term(&Data::Struct {
+        a: 7,
+        b: "x".to_string(),
+    })
+    .unwrap();
+    let (variant, value) = tagged(&term);
+    assert_eq!(variant, &binary("Struct"));
+    let Term::Map(fields) = value else {
+        panic!("{value:?} isn't a map");
+    };
+    assert_eq!(
+        fields.map.get(&binary("a")),
+        Some(&Term::FixInteger(FixInteger::from(7)))
+    );
+    assert_eq!(fields.map.get(&binary("bee")), Some(&binary("x")));
+}
+
+#[test]
+fn data_variants_round_trip() {
+    let variants = [
+        Data::Newtype(7),
+        Data::Tuple(7, "x".to_string()),
+        Data::Struct {
+            a: 7,
+            b: "x".to_string(),
+        },
+        Data::Unit,
+    ];
+
+    for variant in variants {
+        let term = to_term(&variant).unwrap();
+        assert_eq!(from_term::<Data>(term).unwrap(), variant);
+    }
+}
+
+#[test]
+fn tuple_variant_from_tuple() {
+    let term = Term::Map(Map::from([(
+        binary("Tuple"),
+        Term::Tuple(Tuple::from(vec![
+            Term::FixInteger(FixInteger::from(7)),
+            binary("x"),
+        ])),
+    )]));
+    assert_eq!(
+        from_term::<Data>(term).unwrap(),
+        Data::Tuple(7, "x".to_string())
+    );
+}
+
+#[test]
+fn tagged_enum_rejects_multiple_entries() {
+    let term = Term::Map(Map::from([
+        (binary("Newtype"), Term::FixInteger(FixInteger::from(7))),
+        (binary("unit"), binary("unit")),
+    ]));
+    assert!(matches!(from_term::<Data>(term), Err(Error::ExpectedEnum)));
+}
+
+#[test]
+fn data_variant_rejects_missing_data() {
+    for name in ["Newtype", "Tuple", "Struct"] {
+        assert!(from_term::<Data>(binary(name)).is_err());
+    }
+}
+
+#[test]
+fn unit_variant_rejects_data() {
+    let term = Term::Map(Map::from([(binary("unit"), binary("unit"))]));
+    assert!(matches!(
+        from_term::<Data>(term),
+        Err(Error::ExtraneousInput)
+    ));
+}
+
+#[test]
+fn struct_variant_rejects_list() {
+    let term = Term::Map(Map::from([(binary("Struct"), Term::List(List::nil()))]));
+    let error = from_term::<Data>(term).unwrap_err();
+    assert!(matches!(error.inner(), Error::ExpectedMap));
+    assert_eq!(error.path(), [Segment::Field("Struct".to_string())]);
+}
@@ -0,0 +1,272 @@
+use eetf::{BigInteger, FixInteger, Term};
+use serde::de::DeserializeOwned;
+use serde_etf::{from_slice, from_term, to_term, to_vec, Error};
+use std::fmt::Debug;
+use std::io::Cursor;
+
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+// The tag of the first term after the version byte
+fn tag(bytes: &[u8]) -> u8 {
+    bytes[1]
+}
+
+#[test]
+fn narrowest_tag_signed() {
+    for (v, expected) in [
+        (0i64, SMALL_INTEGER_EXT),
+        (255, SMALL_INTEGER_EXT),
+        (256, INTEGER_EXT),
+        (-1, INTEGER_EXT),
+        (i32::MAX as i64, INTEGER_EXT),
+        (i32::MAX as i64 + 1, SMALL_BIG_EXT),
+        (i32::MIN as i64, INTEGER_EXT),
+        (i32::MIN as i64 - 1, SMALL_BIG_EXT),
+        (i64::MAX, SMALL_BIG_EXT),
+        (i64::MIN, SMALL_BIG_EXT),
+    ] {
+        assert_eq!(tag(&to_vec(&v).unwrap()), expected, "{v}");
+    }
+
+    assert_eq!(tag(&to_vec(&-5i8).unwrap()), INTEGER_EXT);
+    assert_eq!(tag(&to_vec(&200i16).unwrap()), SMALL_INTEGER_EXT);
+    assert_eq!(tag(&to_vec(&-70000i32).unwrap()), INTEGER_EXT);
+}
+
+#[test]
+fn narrowest_tag_unsigned() {
+    for (v, expected) in [
+        (0u64, SMALL_INTEGER_EXT),
+        (255, SMALL_INTEGER_EXT),
+        (256, INTEGER_EXT),
+        (i32::MAX as u64, INTEGER_EXT),
+        (i32::MAX as u64 + 1, SMALL_BIG_EXT),
+        (1 << 63, SMALL_BIG_EXT),
+        (u64::MAX, SMALL_BIG_EXT),
+    ] {
+        assert_eq!(tag(&to_vec(&v).unwrap()), expected, "{v}");
+    }
+
+    assert_eq!(tag(&to_vec(&255u8).unwrap()), SMALL_INTEGER_EXT);
+    assert_eq!(tag(&to_vec(&1000u16).unwrap()), INTEGER_EXT);
+    assert_eq!(tag(&to_vec(&4_000_000_000u32).unwrap()), SMALL_BIG_EXT);
+}
+
+#[test]
+fn term_uses_narrowest_integer() {
+    assert_eq!(to_term(&7u8).unwrap(), Term::from(FixInteger::from(7)));
+    assert_eq!(to_term(&7u64).unwrap(), Term::from(FixInteger::from(7)));
+    assert_eq!(
+        to_term(&(i32::MIN as i64)).unwrap(),
+        Term::from(FixInteger::from(i32::MIN))
+    );
+    assert_eq!(
+        to_term(&(i32::MAX as u32 + 1)).unwrap(),
+        Term::from(BigInteger::from(i32::MAX as u32 + 1))
+    );
+    assert_eq!(
+        to_term(&u64::MAX).unwrap(),
+        Term::from(BigInteger::from(u64::MAX))
+    );
+}
+
+#[test]
+fn big_integer_is_minimal() {
+    // 2^63 needs all eight digits
+    assert_eq!(
+        to_vec(&(1u64 << 63)).unwrap(),
+        [131, SMALL_BIG_EXT, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]
+    );
+    // 2^31 only needs four
+    assert_eq!(
+        to_vec(&(1u64 << 31)).unwrap(),
+        [131, SMALL_BIG_EXT, 4, 0, 0, 0, 0, 0x80]
+    );
+    assert_eq!(
+        to_vec(&i64::MIN).unwrap(),
+        [131, SMALL_BIG_EXT, 8, 1, 0, 0, 0, 0, 0, 0, 0, 0x80]
+    );
+}
+
+#[test]
+fn to_vec_matches_term_encoding() {
+    for v in [0, 255, 256, i32::MAX as u64, 1 << 31, 1 << 63, u64::MAX] {
+        let mut buf = Vec::new();
+        to_term(&v).unwrap().encode(&mut buf).unwrap();
+        assert_eq!(to_vec(&v).unwrap(), buf, "{v}");
+    }
+    for v in [-1, -256, i32::MIN as i64, i32::MIN as i64 - 1, i64::MIN] {
+        let mut buf = Vec::new();
+        to_term(&v).unwrap().encode(&mut buf).unwrap();
+        assert_eq!(to_vec(&v).unwrap(), buf, "{v}");
+    }
+}
+
+// Every value is decoded from every tag it can be encoded in, into every Rust
+// integer type, through both deserializers
+fn check<T>(bytes: &[u8], expected: Option<T>)
+where
+    T: DeserializeOwned + PartialEq + Debug,
+{
+    let from_bytes = from_slice::<T>(bytes).ok();
+    let from_term = from_term::<T>(decode(bytes)).ok();
+    assert_eq!(from_bytes, expected, "{bytes:?}");
+    assert_eq!(from_term, expected, "{bytes:?}");
+}
+
+fn check_all(bytes: &[u8], value: i128) {
+    check(bytes, i8::try_from(value).ok());
+    check(bytes, i16::try_from(value).ok());
+    check(bytes, i32::try_from(value).ok());
+    check(bytes, i64::try_from(value).ok());
+    check(bytes, u8::try_from(value).ok());
+    check(bytes, u16::try_from(value).ok());
+    check(bytes, u32::try_from(value).ok());
+    check(bytes, u64::try_from(value).ok());
+    check(bytes, Some(value));
+    check(bytes, u128::try_from(value).ok());
+}
+
+fn small_big(tag: u8, value: i128) -> Vec<u8> {
+    let digits = value.unsigned_abs().to_le_bytes();
+    let mut bytes = vec![131, tag];
+    if tag == SMALL_BIG_EXT {
+        bytes.push(digits.len() as u8);
+    } else {
+        bytes.extend_from_slice(&(digits.len() as u32).to_be_bytes());
+    }
+    bytes.push((value < 0) as u8);
+    // Non-minimal on purpose, trailing zero digits must be ignored
+    bytes.extend_from_slice(&digits);
+    bytes
+}
+
+#[test]
+fn accepts_every_integer_tag() {
+    for value in [
+        0i128,
+        1,
+        -1,
+        127,
+        128,
+        -128,
+        -129,
+        255,
+        256,
+        i32::MAX as i128,
+        i32::MIN as i128,
+    ] {
+        if let Ok(v) = u8::try_from(value) {
+            check_all(&[131, SMALL_INTEGER_EXT, v], value);
+        }
+        let mut bytes = vec![131, INTEGER_EXT];
+        bytes.extend_from_slice(&(value as i32).to_be_bytes());
+        check_all(&bytes, value);
+
+        check_all(&small_big(SMALL_BIG_EXT, value), value);
+        check_all(&small_big(LARGE_BIG_EXT, value), value);
+    }
+
+    for value in [
+        i32::MAX as i128 + 1,
+        i32::MIN as i128 - 1,
+        u32::MAX as i128,
+        i64::MAX as i128,
+        i64::MIN as i128,
+        1 << 63,
+        u64::MAX as i128,
+    ] {
+        check_all(&small_big(SMALL_BIG_EXT, value), value);
+        check_all(&small_big(LARGE_BIG_EXT, value), value);
+    }
+}
+
+#[test]
+fn round_trip_boundaries() {
+    for v in [0u64, 255, 256, i32::MAX as u64, 1 << 31, 1 << 63, u64::MAX] {
+        assert_eq!(from_slice::<u64>(&to_vec(&v).unwrap()).unwrap(), v);
+        assert_eq!(from_term::<u64>(to_term(&v).unwrap()).unwrap(), v);
+    }
+    for v in [
+        -1i64,
+        i32::MIN as i64,
+        i32::MIN as i64 - 1,
+        i64::MIN,
+        i64::MAX,
+    ] {
+        assert_eq!(from_slice::<i64>(&to_vec(&v).unwrap()).unwrap(), v);
+        assert_eq!(from_term::<i64>(to_term(&v).unwrap()).unwrap(), v);
+    }
+}
+
+#[test]
+fn rejects_out_of_range_big_integer() {
+    // 2^64 and -(2^63 + 1) don't fit into any 64 bit integer type
+    let too_large = small_big(SMALL_BIG_EXT, 1 << 64);
+    let too_small = small_big(SMALL_BIG_EXT, i64::MIN as i128 - 1);
+
+    assert!(matches!(
+        from_slice::<u64>(&too_large),
+        Err(Error::NumberTooLarge)
+    ));
+    assert!(matches!(
+        from_slice::<i64>(&too_small),
+        Err(Error::NumberTooSmall)
+    ));
+    assert!(from_term::<u64>(decode(&too_large)).is_err());
+    assert!(from_term::<i64>(decode(&too_small)).is_err());
+}
+
+#[test]
+fn round_trip_128_bit() {
+    for v in [0u128, u64::MAX as u128 + 1, i128::MAX as u128, u128::MAX] {
+        assert_eq!(from_slice::<u128>(&to_vec(&v).unwrap()).unwrap(), v);
+        assert_eq!(from_term::<u128>(to_term(&v).unwrap()).unwrap(), v);
+    }
+    for v in [-1i128, i64::MIN as i128 - 1, i128::MIN, i128::MAX] {
+        assert_eq!(from_slice::<i128>(&to_vec(&v).unwrap()).unwrap(), v);
+        assert_eq!(from_term::<i128>(to_term(&v).unwrap()).unwrap(), v);
+    }
+
+    // Both serializers produce the same bytes as eetf
+    for v in [u64::MAX as i128 + 1, i128::MIN, i128::MAX] {
+        let mut buf = Vec::new();
+        to_term(&v).unwrap().encode(&mut buf).unwrap();
+        assert_eq!(to_vec(&v).unwrap(), buf, "{v}");
+        assert_eq!(tag(&buf), SMALL_BIG_EXT);
+    }
+    // Small values still use the narrowest tag
+    assert_eq!(to_vec(&7u128).unwrap(), [131, SMALL_INTEGER_EXT, 7]);
+    assert_eq!(to_vec(&-7i128).unwrap(), to_vec(&-7i64).unwrap());
+}
+
+#[test]
+fn rejects_out_of_range_128_bit() {
+    // 2^128
+    let mut too_large = vec![131, SMALL_BIG_EXT, 17, 0];
+    too_large.extend_from_slice(&[0; 16]);
+    too_large.push(1);
+    // -(2^127 + 1)
+    let mut too_small = small_big(SMALL_BIG_EXT, i128::MIN);
+    too_small[4] = 1;
+
+    assert!(matches!(
+        from_slice::<u128>(&too_large),
+        Err(Error::NumberTooLarge)
+    ));
+    assert!(matches!(
+        from_slice::<i128>(&too_small),
+        Err(Error::NumberTooSmall)
+    ));
+    assert!(from_term::<u128>(decode(&too_large)).is_err());
+    assert!(from_term::<i128>(decode(&too_small)).is_err());
+    // Negative numbers don't fit into u128 either
+    assert!(from_slice::<u128>(&to_vec(&-1i128).unwrap()).is_err());
+}
@@ -0,0 +1,350 @@
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_slice, from_slice_with, to_vec, to_vec_with, Error, Limit, Limits, Options};
+use std::collections::HashMap;
+
+fn limited(limits: Limits) -> Options {
+    Options::new().limits(limits)
+}
+
+fn exceeds<T>(result: serde_etf::Result<T>, limit: Limit) -> bool {
+    result.is_err_and(|e| matches!(e.inner(), Error::LimitExceeded(l) if *l == limit))
+}
+
+// `{{{...{}...}}}`, `depth` tuples deep
+fn nested_tuples(depth: usize) -> Vec<u8> {
+    let mut bytes = vec![131];
+    for _ in 1..depth {
+        bytes.extend_from_slice(&[104, 1]);
+    }
+    bytes.extend_from_slice(&[104, 0]);
+    bytes
+}
+
+#[test]
+fn depth() {
+    let options = limited(Limits::new().max_depth(3));
+    let value = vec![vec![vec![1u8]]];
+    let bytes = to_vec(&value).unwrap();
+
+    assert_eq!(
+        from_slice_with::<Vec<Vec<Vec<u8>>>>(&bytes, &options).unwrap(),
+        value
+    );
+    assert!(exceeds(
+        from_slice_with::<IgnoredAny>(&to_vec(&vec![value]).unwrap(), &options),
+        Limit::Depth
+    ));
+    // Tagged enum variants count, too
+    let options = limited(Limits::new().max_depth(1));
+    let bytes = to_vec(&Kind::Newtype(7)).unwrap();
+    assert_eq!(
+        from_slice_with::<Kind>(&bytes, &options).unwrap(),
+        Kind::Newtype(7)
+    );
+    let bytes = to_vec(&Kind::Tuple(1, String::new())).unwrap();
+    assert!(exceeds(
+        from_slice_with::<Kind>(&bytes, &options),
+        Limit::Depth
+    ));
+}
+
+#[test]
+fn default_depth_prevents_stack_overflow() {
+    assert!(from_slice::<IgnoredAny>(&nested_tuples(128)).is_ok());
+    assert!(exceeds(
+        from_slice::<IgnoredAny>(&nested_tuples(129)),
+        Limit::Depth
+    ));
+    assert!(exceeds(
+        from_slice::<IgnoredAny>(&nested_tuples(1_000_000)),
+        Limit::Depth
+    ));
+}
+
+#[test]
+fn elements() {
+    let options = limited(Limits::new().max_elements(12));
+
+    let value = vec![vec![1u8; 5]; 2];
+    let bytes = to_vec(&value).unwrap();
+    assert_eq!(
+        from_slice_with::<Vec<Vec<u8>>>(&bytes, &options).unwrap(),
+        value
+    );
+
+    // Counted across the whole term
+    let bytes = to_vec(&vec![vec![1u8; 5]; 3]).unwrap();
+    assert!(exceeds(
+        from_slice_with::<Vec<Vec<u8>>>(&bytes, &options),
+        Limit::Elements
+    ));
+
+    // Map entries count once
+    let value = HashMap::from([(1u8, 2u8), (3, 4)]);
+    let bytes = to_vec(&value).unwrap();
+    assert!(
+        from_slice_with::<HashMap<u8, u8>>(&bytes, &limited(Limits::new().max_elements(2))).is_ok()
+    );
+    assert!(exceeds(
+        from_slice_with::<HashMap<u8, u8>>(&bytes, &limited(Limits::new().max_elements(1))),
+        Limit::Elements
+    ));
+}
+
+#[test]
+fn elements_are_checked_before_reading() {
+    // A list that claims to have u32::MAX elements
+    let bytes = [131, 108, 255, 255, 255, 255];
+    assert!(exceeds(
+        from_slice_with::<Vec<u8>>(&bytes, &limited(Limits::new().max_elements(100))),
+        Limit::Elements
+    ));
+    assert!(matches!(
+        from_slice::<Vec<u8>>(&bytes).unwrap_err().inner(),
+        Error::UnexpectedEof
+    ));
+}
+
+#[test]
+fn binary_size() {
+    let options = limited(Limits::new().max_binary_size(4));
+
+    assert_eq!(
+        from_slice_with::<String>(&to_vec(&"four").unwrap(), &options).unwrap(),
+        "four"
+    );
+    assert!(exceeds(
+        from_slice_with::<String>(&to_vec(&"five!").unwrap(), &options),
+        Limit::BinarySize
+    ));
+    assert!(exceeds(
+        from_slice_with::<u64>(&to_vec(&u64::MAX).unwrap(), &options),
+        Limit::BinarySize
+    ));
+    // Claimed sizes are checked before reading
+    assert!(exceeds(
+        from_slice_with::<IgnoredAny>(&[131, 109, 255, 255, 255, 255], &options),
+        Limit::BinarySize
+    ));
+}
+
+#[test]
+fn size() {
+    // The version byte, tag, length and three bytes
+    let options = limited(Limits::new().max_size(9));
+
+    assert!(from_slice_with::<String>(&to_vec(&"abc").unwrap(), &options).is_ok());
+    assert!(exceeds(
+        from_slice_with::<String>(&to_vec(&"abcd").unwrap(), &options),
+        Limit::Size
+    ));
+}
+
+#[test]
+fn decompressed_size() {
+    let value = vec![0u8; 1000];
+    let bytes = to_vec_with(&value, &Options::new().compress_above(0)).unwrap();
+    assert!(bytes.len() < 100);
+
+    let options = limited(Limits::new().max_size(1000));
+    assert!(exceeds(
+        from_slice_with::<Vec<u8>>(&bytes, &options),
+        Limit::Size
+    ));
+    let options = limited(Limits::new().max_size(4000));
+    assert_eq!(from_slice_with::<Vec<u8>>(&bytes, &options).unwrap(), value);
+
+    // The announced size is checked before decompressing
+    let bomb = [131, 80, 255, 255, 255, 255, 120, 156];
+    assert!(exceeds(
+        from_slice_with::<IgnoredAny>(&bomb, &options),
+        Limit::Size
+    ));
+}
+
+#[test]
+fn display() {
+    assert_eq!(
+        Error::LimitExceeded(Limit::BinarySize).to_string(),
+        "Input exceeds the binary size limit"
+    );
+}
+
+// Fuzzing without any dependencies, seeded so failures can be reproduced
+
+struct Rng(u64);
+
+impl Rng {
+    // xorshift64*
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+enum Kind {
+    Unit,
+    Newtype(u8),
+    Tuple(i64, String),
+    Struct { value: Option<bool> },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Payload {
+    op: u8,
+    d: Option<HashMap<String, Vec<u64>>>,
+    s: Option<i64>,
+    t: Option<String>,
+    kinds: Vec<Kind>,
+    data: (f64, bool, char),
+}
+
+fn payload() -> Payload {
+    Payload {
+        op: 2,
+        d: Some(HashMap::from([
+            ("token".to_string(), vec![1, 1 << 40]),
+            ("intents".to_string(), vec![]),
+        ])),
+        s: Some(-42),
+        t: None,
+        kinds: vec![
+            Kind::Unit,
+            Kind::Newtype(7),
+            Kind::Tuple(i64::MIN, "tuple".to_string()),
+            Kind::Struct { value: Some(true) },
+        ],
+        data: (1.5, false, 'ü'),
+    }
+}
+
+// Decodes into a couple of types, which only must not panic
+fn decode(bytes: &[u8], options: &Options) {
+    let _ = from_slice_with::<IgnoredAny>(bytes, options);
+    let _ = from_slice_with::<Payload>(bytes, options);
+    let _ = from_slice_with::<Vec<Kind>>(bytes, options);
+    let _ = from_slice_with::<HashMap<String, Option<Vec<i32>>>>(bytes, options);
+    let _ = from_slice_with::<(u64, &str, &[u8])>(bytes, options);
+}
+
+fn fuzz_options() -> [Options; 3] {
+    [
+        Options::new(),
+        limited(
+            Limits::new()
+                .max_depth(4)
+                .max_elements(16)
+                .max_binary_size(8)
+                .max_size(256),
+        ),
+        Options::new().bool_atoms(false).null_atom("null"),
+    ]
+}
+
+#[test]
+fn fuzz_random_bytes() {
+    let mut rng = Rng(0x5eed);
+    for _ in 0..10_000 {
+        let len = rng.below(64);
+        let mut bytes = vec![131];
+        bytes.extend((0..len).map(|_| rng.byte()));
+        for options in &fuzz_options() {
+            decode(&bytes, options);
+        }
+    }
+}
+
+#[test]
+fn fuzz_mutated_terms() {
+    let mut rng = Rng(0xfeed);
+    let valid = [
+        to_vec(&payload()).unwrap(),
+        to_vec_with(&payload(), &Options::new().compress_above(0)).unwrap(),
+    ];
+
+    for _ in 0..10_000 {
+        let mut bytes = valid[rng.below(valid.len())].clone();
+        for _ in 0..1 + rng.below(4) {
+            if bytes.len() <= 2 {
+                break;
+            }
+            let at = 2 + rng.below(bytes.len() - 2);
+            match rng.below(4) {
+                0 => bytes[at] = rng.byte(),
+                1 => bytes[at] ^= 1 << rng.below(8),
+                2 => bytes.insert(at, rng.byte()),
+                _ => bytes.truncate(at),
+            }
+        }
+        for options in &fuzz_options() {
+            decode(&bytes, options);
+        }
+    }
+}
+
+// Generates a random, but well-formed term
+fn term(rng: &mut Rng, depth: usize, bytes: &mut Vec<u8>) {
+    if depth > 0 && rng.below(3) == 0 {
+        let len = rng.below(6);
+        match rng.below(3) {
+            0 => {
+                bytes.extend_from_slice(&[104, len as u8]);
+                (0..len).for_each(|_| term(rng, depth - 1, bytes));
+            }
+            1 => {
+                bytes.push(108);
+                bytes.extend_from_slice(&(len as u32).to_be_bytes());
+                (0..len).for_each(|_| term(rng, depth - 1, bytes));
+                bytes.push(106);
+            }
+            _ => {
+                bytes.push(116);
+                bytes.extend_from_slice(&(len as u32).to_be_bytes());
+                (0..2 * len).for_each(|_| term(rng, depth - 1, bytes));
+            }
+        }
+        return;
+    }
+
+    match rng.below(4) {
+        0 => bytes.extend_from_slice(&[97, rng.byte()]),
+        1 => bytes.extend_from_slice(&[119, 3, b'n', b'i', b'l']),
+        2 => {
+            let len = rng.below(12);
+            bytes.push(109);
+            bytes.extend_from_slice(&(len as u32).to_be_bytes());
+            bytes.extend((0..len).map(|_| b'a' + rng.below(26) as u8));
+        }
+        _ => {
+            bytes.push(70);
+            bytes.extend_from_slice(&(rng.next() as f64).to_be_bytes());
+        }
+    }
+}
+
+#[test]
+fn fuzz_generated_terms() {
+    let mut rng = Rng(0xbeef);
+    for _ in 0..5_000 {
+        let mut bytes = vec![131];
+        term(&mut rng, 8, &mut bytes);
+
+        // Well-formed terms are always accepted without limits
+        assert!(from_slice::<IgnoredAny>(&bytes).is_ok(), "{bytes:?}");
+        for options in &fuzz_options() {
+            decode(&bytes, options);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use eetf::{Atom, BigInteger, Binary, FixInteger, Map, Term};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_etf::{
+    from_slice, from_slice_with, from_term, from_term_with, to_term, to_term_with, to_vec,
+    to_vec_with, KeyStyle, Options, SnowflakeStyle,
+};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+// Serializes like twilight-model's `Id`
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Id(u64);
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("Id", &self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Integer(u64),
+            String(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Integer(id) => Ok(Id(id)),
+            Repr::String(id) => id.parse().map(Id).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+enum Kind {
+    Named { value: bool },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Payload {
+    op: u8,
+    t: Option<String>,
+    flag: bool,
+    id: Id,
+    small_id: Id,
+    lookup: HashMap<String, u8>,
+    kind: Kind,
+}
+
+fn payload() -> Payload {
+    Payload {
+        op: 0,
+        t: None,
+        flag: true,
+        id: Id(1 << 60),
+        small_id: Id(7),
+        lookup: HashMap::from([("grüße".to_string(), 1)]),
+        kind: Kind::Named { value: false },
+    }
+}
+
+fn discord() -> Options {
+    Options::new()
+        .key_style(KeyStyle::Atom)
+        .null_atom("null")
+        .bool_atoms(false)
+        .snowflake_style(SnowflakeStyle::Integer)
+}
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+fn atom(name: &str) -> Term {
+    Term::from(Atom::from(name))
+}
+
+fn binary(name: &str) -> Term {
+    Term::from(Binary::from(name.as_bytes()))
+}
+
+fn int(value: i32) -> Term {
+    Term::from(FixInteger::from(value))
+}
+
+fn map(entries: Vec<(Term, Term)>) -> Term {
+    Term::from(Map::from(entries.into_iter().collect::<HashMap<_, _>>()))
+}
+
+#[test]
+fn defaults() {
+    let expected = map(vec![
+        (binary("op"), int(0)),
+        (binary("t"), atom("nil")),
+        (binary("flag"), atom("true")),
+        (binary("id"), binary("1152921504606846976")),
+        (binary("small_id"), binary("7")),
+        (binary("lookup"), map(vec![(binary("grüße"), int(1))])),
+        (
+            binary("kind"),
+            map(vec![(
+                binary("Named"),
+                map(vec![(binary("value"), atom("false"))]),
+            )]),
+        ),
+    ]);
+
+    assert_eq!(to_term(&payload()).unwrap(), expected);
+    assert_eq!(decode(&to_vec(&payload()).unwrap()), expected);
+    assert_eq!(
+        to_term_with(&payload(), &Options::default()).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn custom() {
+    let expected = map(vec![
+        (atom("op"), int(0)),
+        (atom("t"), atom("null")),
+        (atom("flag"), int(1)),
+        (atom("id"), Term::from(BigInteger::from(1u64 << 60))),
+        (atom("small_id"), int(7)),
+        (atom("lookup"), map(vec![(atom("grüße"), int(1))])),
+        (
+            atom("kind"),
+            map(vec![(atom("Named"), map(vec![(atom("value"), int(0))]))]),
+        ),
+    ]);
+
+    assert_eq!(to_term_with(&payload(), &discord()).unwrap(), expected);
+    assert_eq!(
+        decode(&to_vec_with(&payload(), &discord()).unwrap()),
+        expected
+    );
+}
+
+#[test]
+fn round_trip() {
+    for options in [Options::new(), discord()] {
+        let term = to_term_with(&payload(), &options).unwrap();
+        assert_eq!(
+            from_term_with::<Payload>(term, &options).unwrap(),
+            payload()
+        );
+
+        let bytes = to_vec_with(&payload(), &options).unwrap();
+        assert_eq!(
+            from_slice_with::<Payload>(&bytes, &options).unwrap(),
+            payload()
+        );
+    }
+}
+
+#[test]
+fn keys_and_snowflakes_decode_either_way() {
+    // Atom keys and integer snowflakes with the default options
+    let bytes = to_vec_with(
+        &payload(),
+        &Options::new()
+            .key_style(KeyStyle::Atom)
+            .snowflake_style(SnowflakeStyle::Integer),
+    )
+    .unwrap();
+    assert_eq!(from_slice::<Payload>(&bytes).unwrap(), payload());
+    assert_eq!(from_term::<Payload>(decode(&bytes)).unwrap(), payload());
+}
+
+#[test]
+fn null_atom() {
+    let options = Options::new().null_atom("undefined");
+
+    assert_eq!(
+        to_vec_with(&None::<u8>, &options).unwrap()[1..4],
+        [100, 0, 9]
+    );
+    assert_eq!(
+        from_slice_with::<Option<u8>>(&to_vec_with(&None::<u8>, &options).unwrap(), &options)
+            .unwrap(),
+        None
+    );
+    // `nil` is just an atom then
+    assert_eq!(
+        from_slice_with::<Option<String>>(&to_vec(&None::<u8>).unwrap(), &options).unwrap(),
+        Some("nil".to_string())
+    );
+    assert_eq!(
+        from_term_with::<Option<String>>(atom("nil"), &options).unwrap(),
+        Some("nil".to_string())
+    );
+}
+
+#[test]
+fn bool_integers() {
+    let options = Options::new().bool_atoms(false);
+
+    assert_eq!(to_vec_with(&true, &options).unwrap(), [131, 97, 1]);
+    assert!(from_slice_with::<bool>(&[131, 97, 0], &options).is_ok_and(|b| !b));
+    assert!(from_slice_with::<bool>(&[131, 97, 2], &options).is_err());
+    // Atoms are still accepted
+    assert!(from_term_with::<bool>(atom("true"), &options).unwrap());
+    // Integers are only accepted if enabled
+    assert!(from_slice::<bool>(&[131, 97, 1]).is_err());
+    assert!(from_term::<bool>(int(1)).is_err());
+}
+
+#[test]
+fn non_string_keys_stay() {
+    let value = HashMap::from([(1u8, 2u8)]);
+    let options = Options::new().key_style(KeyStyle::Atom);
+
+    assert_eq!(
+        to_term_with(&value, &options).unwrap(),
+        map(vec![(int(1), int(2))])
+    );
+    assert_eq!(
+        decode(&to_vec_with(&value, &options).unwrap()),
+        map(vec![(int(1), int(2))])
+    );
+}
+
+#[test]
+fn long_atoms_become_binaries() {
+    let name = "n".repeat(u16::MAX as usize + 1);
+    let options = Options::new().null_atom(name.clone());
+
+    assert_eq!(
+        decode(&to_vec_with(&None::<u8>, &options).unwrap()),
+        binary(&name)
+    );
+}
@@ -0,0 +1,117 @@
+use serde::Serialize;
+use serde_etf::{
+    etf, from_slice, to_term, to_term_with, to_vec, to_vec_with, KeyStyle, MapOrder, Options, Value,
+};
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+struct Identify {
+    token: &'static str,
+    intents: u32,
+    properties: Properties,
+    compress: bool,
+}
+
+#[derive(Serialize)]
+struct Properties {
+    os: &'static str,
+    browser: &'static str,
+}
+
+fn identify() -> Identify {
+    Identify {
+        token: "token",
+        intents: 513,
+        properties: Properties {
+            os: "linux",
+            browser: "radiance",
+        },
+        compress: false,
+    }
+}
+
+// The keys of a map as they appear in the encoding
+fn keys(bytes: &[u8]) -> Vec<String> {
+    let Value::Map(entries) = from_slice::<Value>(bytes).unwrap() else {
+        panic!("not a map");
+    };
+    entries
+        .into_iter()
+        .map(|(k, _)| match k {
+            Value::String(k) => k,
+            k => k.as_i64().unwrap().to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn struct_fields_in_declaration_order() {
+    for options in [
+        Options::new(),
+        Options::new().key_style(KeyStyle::Atom),
+        Options::new().map_order(MapOrder::Sorted),
+    ] {
+        let bytes = to_vec_with(&identify(), &options).unwrap();
+        assert_eq!(keys(&bytes), ["token", "intents", "properties", "compress"]);
+        let properties = to_vec(&from_slice::<Value>(&bytes).unwrap()["properties"]).unwrap();
+        assert_eq!(keys(&properties), ["os", "browser"]);
+    }
+}
+
+#[test]
+fn maps_in_insertion_order() {
+    let value = etf!(#{ "b" => 1, "a" => 2, "c" => 3 });
+    assert_eq!(keys(&to_vec(&value).unwrap()), ["b", "a", "c"]);
+}
+
+#[test]
+fn sorted_maps() {
+    let options = Options::new().map_order(MapOrder::Sorted);
+
+    let value = etf!(#{ "b" => 1, :c => 2, "a" => #{ 10 => 0, -1 => 0, 1u64 << 40 => 0 } });
+    let bytes = to_vec_with(&value, &options).unwrap();
+    // Atoms sort before binaries, and nested maps are sorted, too
+    assert_eq!(keys(&bytes), ["c", "a", "b"]);
+    let nested = to_vec(&from_slice::<Value>(&bytes).unwrap()["a"]).unwrap();
+    assert_eq!(keys(&nested), ["-1", "10", "1099511627776"]);
+
+    // Equal maps encode to the same bytes, no matter how they were built
+    let entries = (0..100).map(|i| (format!("key{i}"), i));
+    let first = HashMap::<_, _>::from_iter(entries.clone());
+    let second = HashMap::<_, _>::from_iter(entries.rev());
+    assert_eq!(
+        to_vec_with(&first, &options).unwrap(),
+        to_vec_with(&second, &options).unwrap()
+    );
+    assert_eq!(
+        from_slice::<HashMap<String, i32>>(&to_vec_with(&first, &options).unwrap()).unwrap(),
+        first
+    );
+}
+
+#[test]
+fn sorted_maps_are_compressed() {
+    let options = Options::new().map_order(MapOrder::Sorted).compress_above(0);
+    let map = HashMap::<_, _>::from_iter((0..100).map(|i| (i, "value")));
+
+    let bytes = to_vec_with(&map, &options).unwrap();
+    assert_eq!(bytes[1], 80);
+    let Value::Map(entries) = from_slice::<Value>(&bytes).unwrap() else {
+        panic!("not a map");
+    };
+    let keys = entries.iter().map(|(k, _)| k.as_i64().unwrap());
+    assert!(keys.eq(0..100));
+}
+
+#[test]
+fn terms_ignore_map_order() {
+    let options = Options::new().map_order(MapOrder::Sorted);
+    let value = etf!(#{ "b" => 1, "a" => 2 });
+
+    // Terms can't keep an order, only the byte encoder sorts
+    assert_eq!(
+        to_term_with(&value, &options).unwrap(),
+        to_term(&value).unwrap()
+    );
+    assert_eq!(keys(&to_vec_with(&value, &options).unwrap()), ["a", "b"]);
+}
@@ -0,0 +1,192 @@
+use eetf::Term;
+use serde::{Deserialize, Serialize};
+use serde_etf::{from_slice, from_term, to_term, to_vec, Error, Segment};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Properties {
+    os: String,
+    client_build_number: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Identify {
+    token: String,
+    properties: Properties,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Payload<T> {
+    op: u8,
+    d: T,
+}
+
+// The same payload, but with a string where the build number should be
+#[derive(Serialize)]
+struct WrongProperties {
+    os: String,
+    client_build_number: String,
+}
+
+#[derive(Serialize)]
+struct WrongIdentify {
+    token: String,
+    properties: WrongProperties,
+}
+
+fn wrong_identify() -> Payload<WrongIdentify> {
+    Payload {
+        op: 2,
+        d: WrongIdentify {
+            token: "token".to_string(),
+            properties: WrongProperties {
+                os: "Linux".to_string(),
+                client_build_number: "12345".to_string(),
+            },
+        },
+    }
+}
+
+fn field(name: &str) -> Segment {
+    Segment::Field(name.to_string())
+}
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+#[test]
+fn decode_error_has_path() {
+    let bytes = to_vec(&wrong_identify()).unwrap();
+
+    for error in [
+        from_slice::<Payload<Identify>>(&bytes).unwrap_err(),
+        from_term::<Payload<Identify>>(decode(&bytes)).unwrap_err(),
+    ] {
+        assert!(matches!(error.inner(), Error::ExpectedInt));
+        assert_eq!(
+            error.path(),
+            [
+                field("d"),
+                field("properties"),
+                field("client_build_number")
+            ]
+        );
+        assert_eq!(error.tag(), Some(109));
+        assert_eq!(
+            error.to_string(),
+            "Expected integer at d.properties.client_build_number (BINARY_EXT)"
+        );
+    }
+}
+
+#[test]
+fn decode_error_has_index() {
+    let bytes = to_vec(&vec![vec![1i32], vec![2, -3]]).unwrap();
+
+    for error in [
+        from_slice::<Vec<Vec<u8>>>(&bytes).unwrap_err(),
+        from_term::<Vec<Vec<u8>>>(decode(&bytes)).unwrap_err(),
+    ] {
+        assert_eq!(error.path(), [Segment::Index(1), Segment::Index(1)]);
+        assert_eq!(error.tag(), Some(98));
+        assert!(error.to_string().ends_with(" at [1][1] (INTEGER_EXT)"));
+    }
+}
+
+#[test]
+fn decode_error_in_variant() {
+    #[derive(Debug, Deserialize, Serialize)]
+    enum Event {
+        Ready { sessions: Vec<u8> },
+    }
+
+    #[derive(Serialize)]
+    enum WrongEvent {
+        Ready { sessions: Vec<&'static str> },
+    }
+
+    let bytes = to_vec(&WrongEvent::Ready {
+        sessions: vec!["session"],
+    })
+    .unwrap();
+
+    for error in [
+        from_slice::<Event>(&bytes).unwrap_err(),
+        from_term::<Event>(decode(&bytes)).unwrap_err(),
+    ] {
+        assert_eq!(
+            error.path(),
+            [field("Ready"), field("sessions"), Segment::Index(0)]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Expected integer at Ready.sessions[0] (BINARY_EXT)"
+        );
+    }
+}
+
+#[test]
+fn missing_field_is_located_at_the_struct() {
+    let bytes = to_vec(&Payload {
+        op: 2,
+        d: BTreeMap::from([("token", "token")]),
+    })
+    .unwrap();
+
+    let error = from_slice::<Payload<Identify>>(&bytes).unwrap_err();
+    assert_eq!(error.path(), [field("d")]);
+    assert_eq!(error.tag(), Some(116));
+    assert_eq!(
+        error.to_string(),
+        "missing field `properties` at d (MAP_EXT)"
+    );
+}
+
+#[test]
+fn top_level_errors_are_not_wrapped() {
+    let error = from_slice::<u8>(&to_vec(&"string").unwrap()).unwrap_err();
+    assert!(matches!(error, Error::ExpectedInt));
+    assert!(error.path().is_empty());
+    assert_eq!(error.tag(), None);
+}
+
+#[test]
+fn encode_error_has_path() {
+    #[derive(Serialize)]
+    enum Activity {
+        Custom(f64, f64),
+    }
+
+    #[derive(Serialize)]
+    struct Presence {
+        activities: BTreeMap<&'static str, Activity>,
+    }
+
+    let presence = Presence {
+        activities: BTreeMap::from([("game", Activity::Custom(1.0, f64::NAN))]),
+    };
+
+    let errors = [
+        to_vec(&presence).unwrap_err(),
+        to_term(&presence).unwrap_err(),
+    ];
+    for error in errors {
+        assert!(matches!(error.inner(), Error::NonFiniteFloat));
+        assert_eq!(
+            error.path(),
+            [
+                field("activities"),
+                field("game"),
+                field("Custom"),
+                Segment::Index(1)
+            ]
+        );
+        assert_eq!(error.tag(), None);
+        assert_eq!(
+            error.to_string(),
+            "Tried to encode a non-finite float at activities.game.Custom[1]"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use eetf::{Atom, List, Term};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_etf::{
+    from_slice, from_slice_with, from_term, to_term, Error, Limit, Limits, Options, Value,
+};
+use std::io::Cursor;
+
+const STRING_EXT: u8 = 107;
+
+// A term as Erlang encodes a list of small integers
+fn byte_list(bytes: &[u8]) -> Vec<u8> {
+    let mut term = vec![131, STRING_EXT];
+    term.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    term.extend_from_slice(bytes);
+    term
+}
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+#[test]
+fn byte_list_as_string() {
+    let bytes = byte_list(b"linux");
+
+    assert_eq!(from_slice::<String>(&bytes).unwrap(), "linux");
+    assert_eq!(from_slice::<&str>(&bytes).unwrap(), "linux");
+    assert_eq!(from_term::<String>(decode(&bytes)).unwrap(), "linux");
+
+    // Every byte is a code point
+    let bytes = byte_list(&[b'h', 0xe9]);
+    assert_eq!(from_slice::<String>(&bytes).unwrap(), "hé");
+    assert_eq!(from_term::<String>(decode(&bytes)).unwrap(), "hé");
+    assert!(from_slice::<&str>(&bytes).is_err());
+}
+
+#[test]
+fn byte_list_as_bytes() {
+    let bytes = byte_list(&[0, 1, 255]);
+
+    assert_eq!(from_slice::<&[u8]>(&bytes).unwrap(), [0, 1, 255]);
+    assert_eq!(from_slice::<Vec<u8>>(&bytes).unwrap(), [0, 1, 255]);
+    assert_eq!(from_term::<Vec<u8>>(decode(&bytes)).unwrap(), [0, 1, 255]);
+    assert_eq!(
+        from_slice::<Vec<u32>>(&bytes).unwrap(),
+        from_term::<Vec<u32>>(decode(&bytes)).unwrap()
+    );
+    assert_eq!(from_slice::<(u8, u8, u8)>(&bytes).ok(), None);
+}
+
+#[test]
+fn byte_list_in_struct() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Properties {
+        os: String,
+        flags: Vec<u8>,
+    }
+
+    // eetf encodes lists of small integers as STRING_EXT, too
+    let term = Term::from(eetf::Map::from([
+        (
+            Term::from(Atom::from("os")),
+            Term::from(List::from(vec![Term::from(eetf::FixInteger::from(
+                b'o' as i32,
+            ))])),
+        ),
+        (
+            Term::from(Atom::from("flags")),
+            to_term(&vec![1u8, 2, 3]).unwrap(),
+        ),
+    ]));
+    let mut bytes = Vec::new();
+    term.encode(&mut bytes).unwrap();
+    assert!(bytes.contains(&STRING_EXT));
+
+    let expected = Properties {
+        os: "o".to_string(),
+        flags: vec![1, 2, 3],
+    };
+    assert_eq!(from_slice::<Properties>(&bytes).unwrap(), expected);
+    assert_eq!(from_term::<Properties>(decode(&bytes)).unwrap(), expected);
+}
+
+#[test]
+fn byte_list_as_any() {
+    // Without a type, it's impossible to tell strings from lists of integers
+    let bytes = byte_list(b"ab");
+    let expected = Value::List(vec![Value::Integer(97), Value::Integer(98)]);
+
+    assert_eq!(from_slice::<Value>(&bytes).unwrap(), expected);
+    assert_eq!(from_term::<Value>(decode(&bytes)).unwrap(), expected);
+    assert!(from_slice::<IgnoredAny>(&bytes).is_ok());
+}
+
+#[test]
+fn byte_list_limits() {
+    let bytes = byte_list(b"abcde");
+
+    let options = Options::new().limits(Limits::new().max_binary_size(4));
+    assert!(matches!(
+        from_slice_with::<String>(&bytes, &options),
+        Err(Error::LimitExceeded(Limit::BinarySize))
+    ));
+    // Visited as a list, every byte is an element
+    let options = Options::new().limits(Limits::new().max_elements(4));
+    assert!(matches!(
+        from_slice_with::<Vec<u8>>(&bytes, &options),
+        Err(Error::LimitExceeded(Limit::Elements))
+    ));
+    assert!(from_slice_with::<String>(&bytes, &options).is_ok());
+}
+
+#[test]
+fn rejects_improper_list() {
+    // [1 | 2]
+    let bytes = [131, 108, 0, 0, 0, 1, 97, 1, 97, 2];
+
+    for error in [
+        from_slice::<Vec<u8>>(&bytes).unwrap_err(),
+        from_slice::<Value>(&bytes).unwrap_err(),
+        from_term::<Vec<u8>>(decode(&bytes)).unwrap_err(),
+        from_term::<Value>(decode(&bytes)).unwrap_err(),
+    ] {
+        assert!(matches!(error, Error::ImproperList));
+    }
+    assert_eq!(
+        Error::ImproperList.to_string(),
+        "Improper lists can't be represented in Rust"
+    );
+}
+
+#[test]
+fn unit_is_nil() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Marker;
+
+    let nil = [131, 119, 3, b'n', b'i', b'l'];
+    let ok = [131, 119, 2, b'o', b'k'];
+
+    from_slice::<()>(&nil).unwrap();
+    from_term::<()>(decode(&nil)).unwrap();
+    assert_eq!(from_term::<Marker>(decode(&nil)).unwrap(), Marker);
+    assert!(matches!(from_slice::<()>(&ok), Err(Error::ExpectedNil)));
+    assert!(matches!(
+        from_term::<()>(decode(&ok)),
+        Err(Error::ExpectedNil)
+    ));
+
+    // The null atom is configurable
+    let options = Options::new().null_atom("null");
+    let null = Term::from(Atom::from("null"));
+    serde_etf::from_term_with::<()>(null, &options).unwrap();
+}
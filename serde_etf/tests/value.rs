@@ -0,0 +1,190 @@
+use eetf::{Atom, BigInteger, Binary, FixInteger, List, Map, Term, Tuple};
+use serde::Deserialize;
+use serde_etf::{etf, from_slice, from_term, to_term, to_vec, Value};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+fn decode(bytes: &[u8]) -> Term {
+    Term::decode(Cursor::new(bytes)).unwrap()
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+fn atom(name: &str) -> Value {
+    Value::Atom(name.to_string())
+}
+
+#[test]
+fn macro_literals() {
+    let id = 1u64 << 40;
+    let name = "radiance";
+
+    assert_eq!(etf!(nil), Value::Nil);
+    assert_eq!(etf!(true), Value::Bool(true));
+    assert_eq!(etf!(-1), Value::Integer(-1));
+    assert_eq!(etf!(1.5), Value::Float(1.5));
+    assert_eq!(etf!(:ok), atom("ok"));
+    assert_eq!(etf!(name), string("radiance"));
+    assert_eq!(etf!([]), Value::List(vec![]));
+    assert_eq!(etf!({}), Value::Tuple(vec![]));
+    assert_eq!(etf!(#{}), Value::Map(vec![]));
+    assert_eq!(
+        etf!([1, :two, "three", id + 1, nil,]),
+        Value::List(vec![
+            Value::Integer(1),
+            atom("two"),
+            string("three"),
+            Value::Integer((1 << 40) + 1),
+            Value::Nil,
+        ])
+    );
+    assert_eq!(
+        etf!(#{ :op => 0, "d" => { :ok, [id] }, 3 => #{} }),
+        Value::Map(vec![
+            (atom("op"), Value::Integer(0)),
+            (
+                string("d"),
+                Value::Tuple(vec![atom("ok"), Value::List(vec![Value::Integer(1 << 40)])])
+            ),
+            (Value::Integer(3), Value::Map(vec![])),
+        ])
+    );
+}
+
+#[test]
+fn indexing() {
+    let value = etf!(#{ :op => 0, "d" => #{ "guilds" => [{ :a, :b }] } });
+
+    assert_eq!(value["op"], Value::from(0));
+    assert_eq!(value["d"]["guilds"][0][1], atom("b"));
+    assert_eq!(
+        value.get("d").and_then(|d| d.get("guilds")),
+        Some(&value["d"]["guilds"])
+    );
+    // Missing keys and indices, or indexing the wrong type, are nil
+    assert_eq!(value["t"], Value::Nil);
+    assert_eq!(value["d"]["guilds"][1], Value::Nil);
+    assert_eq!(value["op"]["nested"], Value::Nil);
+    assert_eq!(value.get("t"), None);
+    assert_eq!(value["op"].as_u64(), Some(0));
+    assert_eq!(value["d"]["guilds"][0][0].as_str(), Some("a"));
+}
+
+#[test]
+fn serializes_like_erlang() {
+    let value = etf!(#{
+        :op => 0,
+        "t" => nil,
+        "d" => [{ :ok, true }, 1u64 << 40, -1.5],
+    });
+    let mut map = HashMap::new();
+    map.insert(
+        Term::from(Atom::from("op")),
+        Term::from(FixInteger::from(0)),
+    );
+    map.insert(
+        Term::from(Binary::from("t".as_bytes())),
+        Term::from(Atom::from("nil")),
+    );
+    map.insert(
+        Term::from(Binary::from("d".as_bytes())),
+        Term::from(List::from(vec![
+            Term::from(Tuple::from(vec![
+                Term::from(Atom::from("ok")),
+                Term::from(Atom::from("true")),
+            ])),
+            Term::from(BigInteger::from(1u64 << 40)),
+            Term::from(eetf::Float::try_from(-1.5).unwrap()),
+        ])),
+    );
+    let expected = Term::from(Map::from(map));
+
+    assert_eq!(to_term(&value).unwrap(), expected);
+    assert_eq!(decode(&to_vec(&value).unwrap()), expected);
+
+    let binary = Value::Binary(vec![0xff, 0]);
+    assert_eq!(
+        to_term(&binary).unwrap(),
+        Term::from(Binary::from(vec![0xff, 0]))
+    );
+}
+
+#[test]
+fn deserializes_any_term() {
+    let value = etf!(#{
+        :op => 0,
+        "t" => nil,
+        "d" => [{ :ok, true, false }, 1u64 << 40, "name"],
+    });
+    // Atoms other than booleans and nil become strings, tuples become lists
+    let expected = etf!(#{
+        "op" => 0,
+        "t" => nil,
+        "d" => [["ok", true, false], 1u64 << 40, "name"],
+    });
+
+    assert_eq!(
+        from_slice::<Value>(&to_vec(&value).unwrap()).unwrap(),
+        expected
+    );
+    // Entries are in whatever order the term has them in
+    let Value::Map(mut entries) = from_term::<Value>(to_term(&value).unwrap()).unwrap() else {
+        panic!("not a map");
+    };
+    entries.sort_by_key(|(k, _)| k.as_str().map(str::to_string));
+    assert_eq!(Value::Map(entries)["d"], expected["d"]);
+
+    assert_eq!(
+        from_slice::<Value>(&to_vec(&Value::Binary(vec![0xff])).unwrap()).unwrap(),
+        Value::Binary(vec![0xff])
+    );
+}
+
+#[test]
+fn partially_typed_payload() {
+    #[derive(Deserialize)]
+    struct Payload {
+        op: u8,
+        d: Value,
+    }
+
+    let bytes = to_vec(&etf!(#{ :op => 2, :d => #{ :token => "abc", :intents => 513 } })).unwrap();
+    let payload = from_slice::<Payload>(&bytes).unwrap();
+
+    assert_eq!(payload.op, 2);
+    assert_eq!(payload.d["token"].as_str(), Some("abc"));
+    assert_eq!(payload.d["intents"].as_u64(), Some(513));
+}
+
+#[test]
+fn json_conversions() {
+    let json = json!({
+        "op": 0,
+        "s": null,
+        "d": { "flags": [true, -1, 1.5, u64::MAX], "name": "radiance" },
+    });
+    let value = Value::from(json.clone());
+
+    assert_eq!(value["d"]["flags"][3], Value::Integer(u64::MAX.into()));
+    assert_eq!(value["s"], Value::Nil);
+    assert_eq!(serde_json::Value::from(value.clone()), json);
+    // Going through ETF and back doesn't change anything either
+    let value = from_slice::<Value>(&to_vec(&value).unwrap()).unwrap();
+    assert_eq!(serde_json::Value::from(value), json);
+}
+
+#[test]
+fn json_conversion_matches_serialization() {
+    let value = etf!(#{
+        :op => 0,
+        "d" => { :ok, [1, 2] },
+        7 => Value::Binary(vec![0xff]),
+    });
+    let json = json!({ "op": 0, "d": ["ok", [1, 2]], "7": [255] });
+
+    assert_eq!(serde_json::to_value(&value).unwrap(), json);
+    assert_eq!(serde_json::Value::from(value), json);
+}
@@ -0,0 +1,280 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use log::{debug, warn};
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use twilight_model::gateway::event::{DispatchEventWithTypeDeserializer, Event};
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, UserMarker};
+use twilight_model::id::Id;
+
+// How many events may be waiting, before they're dropped
+const CAPACITY: usize = 1024;
+
+/// Who an event is meant for. Sessions subscribe to topics, so they get
+/// everything published to them.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    User(Id<UserMarker>),
+    Guild(Id<GuildMarker>),
+    Channel(Id<ChannelMarker>),
+}
+
+/// A dispatch event, as it's sent between binaries. The data is kept as JSON,
+/// so it can be parsed based on its type on the other end.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BusEvent {
+    pub topic: Topic,
+    pub t: String,
+    pub d: serde_json::Value,
+}
+
+impl BusEvent {
+    pub fn new<T: Serialize>(topic: Topic, t: &str, d: &T) -> serde_json::Result<Self> {
+        Ok(BusEvent {
+            topic,
+            t: t.to_string(),
+            d: serde_json::to_value(d)?,
+        })
+    }
+
+    pub fn event(&self) -> serde_json::Result<Event> {
+        let dispatch = DispatchEventWithTypeDeserializer::new(&self.t).deserialize(&self.d)?;
+
+        Ok(Event::from(dispatch))
+    }
+}
+
+pub trait EventBus {
+    /// Publishes an event without waiting. Events nobody is listening for are
+    /// dropped.
+    fn publish(&self, event: BusEvent);
+}
+
+/// Delivers events to subscribers in the same process.
+#[derive(Clone)]
+pub struct LocalBus {
+    sender: broadcast::Sender<BusEvent>,
+}
+
+impl LocalBus {
+    pub fn new() -> Self {
+        LocalBus {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BusEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for LocalBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus for LocalBus {
+    fn publish(&self, event: BusEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Publishes events to a [`LocalBus`] in another process, which [`listen`]s
+/// on a Unix socket. Events are sent as lines of JSON.
+#[derive(Clone)]
+pub struct SocketBus {
+    sender: mpsc::Sender<BusEvent>,
+}
+
+impl SocketBus {
+    /// Connects lazily, and reconnects whenever the connection is lost, so the
+    /// other process doesn't have to be running yet.
+    pub fn connect(path: impl Into<PathBuf>) -> Self {
+        let (sender, events) = mpsc::channel(CAPACITY);
+
+        tokio::spawn(Self::run(path.into(), events));
+
+        SocketBus { sender }
+    }
+
+    async fn run(path: PathBuf, mut events: mpsc::Receiver<BusEvent>) {
+        let mut stream = None;
+
+        while let Some(event) = events.recv().await {
+            let mut line = match serde_json::to_vec(&event) {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("Can't encode {} event: {e}", event.t);
+                    continue;
+                }
+            };
+            line.push(b'\n');
+
+            // A stale connection only shows when writing, so retry once
+            for _ in 0..2 {
+                let connected = match stream.as_mut() {
+                    Some(stream) => stream,
+                    None => match UnixStream::connect(&path).await {
+                        Ok(connected) => stream.insert(connected),
+                        Err(e) => {
+                            warn!("Can't connect to event bus at {}: {e}", path.display());
+                            break;
+                        }
+                    },
+                };
+
+                match connected.write_all(&line).await {
+                    Ok(()) => break,
+                    Err(_) => stream = None,
+                }
+            }
+        }
+    }
+}
+
+impl EventBus for SocketBus {
+    fn publish(&self, event: BusEvent) {
+        if self.sender.try_send(event).is_err() {
+            warn!("Event bus is backed up, dropping event");
+        }
+    }
+}
+
+/// Reads `RADIANCE_BUS_SOCKET`, falling back to `radiance.sock`.
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("RADIANCE_BUS_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("radiance.sock"))
+}
+
+// Only sockets are removed, in case the path names something else by mistake
+fn remove_socket(path: &Path) {
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Publishes everything [`SocketBus`]es send to `path` on `bus`.
+pub fn listen(path: &Path, bus: LocalBus) -> std::io::Result<JoinHandle<()>> {
+    // Left behind by a previous run
+    remove_socket(path);
+    let listener = UnixListener::bind(path)?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Can't accept event bus connection: {e}");
+                    continue;
+                }
+            };
+
+            tokio::spawn(forward(stream, bus.clone()));
+        }
+    }))
+}
+
+async fn forward(stream: UnixStream, bus: LocalBus) {
+    let mut lines = BufReader::new(stream).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        match serde_json::from_str(&line) {
+            Ok(event) => bus.publish(event),
+            Err(e) => warn!("Invalid event bus message: {e}"),
+        }
+    }
+
+    debug!("Event bus connection closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use twilight_model::gateway::payload::incoming::TypingStart;
+
+    fn typing_start() -> BusEvent {
+        let typing = TypingStart {
+            channel_id: Id::new(2),
+            guild_id: None,
+            member: None,
+            timestamp: 1700000000,
+            user_id: Id::new(1),
+        };
+
+        BusEvent::new(Topic::Channel(Id::new(2)), "TYPING_START", &typing).unwrap()
+    }
+
+    #[test]
+    fn parses_events_by_type() {
+        let Event::TypingStart(typing) = typing_start().event().unwrap() else {
+            panic!("Expected TYPING_START");
+        };
+        assert_eq!(typing.user_id, Id::new(1));
+
+        let mut event = typing_start();
+        event.t = "MESSAGE_CREATE".to_string();
+        assert!(event.event().is_err());
+    }
+
+    #[tokio::test]
+    async fn local_bus_delivers_to_every_subscriber() {
+        let bus = LocalBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+
+        bus.publish(typing_start());
+        assert_eq!(first.recv().await.unwrap(), typing_start());
+        assert_eq!(second.recv().await.unwrap(), typing_start());
+    }
+
+    #[tokio::test]
+    async fn socket_bus_publishes_to_the_listener() {
+        let path = std::env::temp_dir().join(format!("radiance-{}.sock", std::process::id()));
+
+        let bus = LocalBus::new();
+        let mut events = bus.subscribe();
+        let listener = listen(&path, bus).unwrap();
+
+        let socket = SocketBus::connect(&path);
+        socket.publish(typing_start());
+        let mut other = typing_start();
+        other.topic = Topic::User(Id::new(1));
+        socket.publish(other.clone());
+
+        for expected in [typing_start(), other] {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event, expected);
+        }
+
+        listener.abort();
+        remove_socket(&path);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn doesnt_remove_other_files() {
+        let path = std::env::temp_dir().join(format!("radiance-{}.txt", std::process::id()));
+        std::fs::write(&path, "not a socket").unwrap();
+
+        assert!(listen(&path, LocalBus::new()).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,65 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::error::GatewayError;
+use flate2::{Compress, Compression, FlushCompress};
+
+pub struct Compressor {
+    compress: Compress,
+    buffer: Box<[u8]>,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compressor {
+    const BUFFER_SIZE: usize = 32 * 1024;
+
+    pub fn new() -> Compressor {
+        Compressor {
+            compress: Compress::new(Compression::default(), true),
+            buffer: vec![0; Self::BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn compress(&mut self, buffer: Vec<u8>) -> Result<Vec<u8>, GatewayError> {
+        let processed_pre = self.compress.total_in();
+
+        let mut processed = 0;
+
+        // Decompressed message. `Vec::extend_from_slice` efficiently allocates
+        // only what's necessary.
+        let mut compressed = Vec::new();
+
+        loop {
+            let produced_pre = self.compress.total_out();
+
+            // Use Sync to ensure data is flushed to the buffer.
+            self.compress
+                .compress(&buffer[processed..], &mut self.buffer, FlushCompress::Sync)
+                .map_err(|_| GatewayError::CompressionError)?;
+
+            processed = (self.compress.total_in() - processed_pre)
+                .try_into()
+                .unwrap();
+            let produced = (self.compress.total_out() - produced_pre)
+                .try_into()
+                .unwrap();
+
+            compressed.extend_from_slice(&self.buffer[..produced]);
+
+            // Break when message has been fully decompressed.
+            if processed == buffer.len() {
+                break;
+            }
+        }
+
+        Ok(compressed)
+    }
+}
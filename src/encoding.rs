@@ -13,6 +13,22 @@ pub enum Encoding {
     Json,
 }
 
+// Discord uses atoms for map keys and integers for snowflakes, which the
+// client's ETF decoder turns into strings. Client payloads are small, so
+// anything large is rejected before it's decoded
+pub fn etf_options() -> serde_etf::Options {
+    serde_etf::Options::new()
+        .key_style(serde_etf::KeyStyle::Atom)
+        .snowflake_style(serde_etf::SnowflakeStyle::Integer)
+        .limits(
+            serde_etf::Limits::new()
+                .max_depth(32)
+                .max_elements(4096)
+                .max_binary_size(16 * 1024)
+                .max_size(64 * 1024),
+        )
+}
+
 impl FromStr for Encoding {
     type Err = ();
 
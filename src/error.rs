@@ -0,0 +1,83 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum GatewayError {
+    ConnectionClosed,
+    InvalidEncoding,
+    IncompleteData,
+    EncodeError,
+    CompressionError,
+    LimitExceeded,
+    SessionTimeout,
+    UnknownOpcode,
+    NotAuthenticated,
+    AuthenticationFailed,
+    AlreadyAuthenticated,
+    InvalidSequence,
+    RateLimited,
+    InvalidApiVersion,
+    // The client didn't keep up with its outbound queue
+    QueueFull,
+    // Another connection resumed the session
+    SessionReplaced,
+}
+
+impl GatewayError {
+    /// The close code and reason sent to the client, using Discord's gateway
+    /// close codes. Closed connections can't be told anything.
+    pub fn close_frame(&self) -> Option<(u16, &'static str)> {
+        Some(match self {
+            GatewayError::ConnectionClosed => return None,
+            GatewayError::EncodeError
+            | GatewayError::CompressionError
+            | GatewayError::QueueFull
+            | GatewayError::SessionReplaced => (4000, "Unknown error"),
+            GatewayError::UnknownOpcode => (4001, "Unknown opcode"),
+            GatewayError::InvalidEncoding
+            | GatewayError::IncompleteData
+            | GatewayError::LimitExceeded => (4002, "Decode error"),
+            GatewayError::NotAuthenticated => (4003, "Not authenticated"),
+            GatewayError::AuthenticationFailed => (4004, "Authentication failed"),
+            GatewayError::AlreadyAuthenticated => (4005, "Already authenticated"),
+            GatewayError::InvalidSequence => (4007, "Invalid seq"),
+            GatewayError::RateLimited => (4008, "Rate limited"),
+            GatewayError::SessionTimeout => (4009, "Session timed out"),
+            GatewayError::InvalidApiVersion => (4012, "Invalid API version"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GatewayError;
+
+    #[test]
+    fn close_codes() {
+        let codes = [
+            (GatewayError::EncodeError, 4000),
+            (GatewayError::CompressionError, 4000),
+            (GatewayError::QueueFull, 4000),
+            (GatewayError::SessionReplaced, 4000),
+            (GatewayError::UnknownOpcode, 4001),
+            (GatewayError::InvalidEncoding, 4002),
+            (GatewayError::IncompleteData, 4002),
+            (GatewayError::LimitExceeded, 4002),
+            (GatewayError::NotAuthenticated, 4003),
+            (GatewayError::AuthenticationFailed, 4004),
+            (GatewayError::AlreadyAuthenticated, 4005),
+            (GatewayError::InvalidSequence, 4007),
+            (GatewayError::RateLimited, 4008),
+            (GatewayError::SessionTimeout, 4009),
+            (GatewayError::InvalidApiVersion, 4012),
+        ];
+
+        for (error, code) in codes {
+            assert_eq!(error.close_frame().map(|(code, _)| code), Some(code));
+        }
+        assert_eq!(GatewayError::ConnectionClosed.close_frame(), None);
+    }
+}
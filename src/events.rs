@@ -4,19 +4,29 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+#[cfg(test)]
+pub mod fixtures;
+mod guild_member_list_update;
 mod identify;
+mod lazy_request;
 pub mod models;
 mod presence_update;
 mod ready;
 mod ready_supplemental;
+mod request_guild_members;
 mod resume;
+mod user_presence_update;
 mod voice_state_update;
 
+pub use guild_member_list_update::*;
 pub use identify::*;
+pub use lazy_request::*;
 pub use presence_update::*;
 pub use ready::*;
 pub use ready_supplemental::*;
+pub use request_guild_members::*;
 pub use resume::*;
+pub use user_presence_update::*;
 pub use voice_state_update::*;
 
 use models::Session;
@@ -28,18 +38,22 @@ use twilight_model::gateway::OpCode;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum RadianceEvent {
-    Identify(Identify),
+    GuildMemberListUpdate(GuildMemberListUpdate),
+    Identify(Box<Identify>),
+    LazyRequest(LazyRequest),
     PresenceUpdate(PresenceUpdate),
-    Ready(Ready),
+    Ready(Box<Ready>),
     ReadySupplemental(ReadySupplemental),
+    RequestGuildMembers(RequestGuildMembers),
     Resume(Resume),
     SessionsReplace(Vec<Session>),
     Twilight(Event),
+    UserPresenceUpdate(UserPresenceUpdate),
     VoiceStateUpdate(VoiceStateUpdate),
 }
 
 // Serialize is implemented below, because Event doesn't directly implement it
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct EventPayload {
     op: OpCode,
     d: Option<RadianceEvent>,
@@ -56,6 +70,10 @@ impl EventPayload {
             t: None,
         }
     }
+
+    pub fn sequence(&self) -> Option<u32> {
+        self.s
+    }
 }
 
 pub trait IntoPayload {
@@ -149,9 +167,11 @@ impl Serialize for EventPayload {
             }?;
         } else {
             match &self.d {
+                Some(RadianceEvent::GuildMemberListUpdate(u)) => state.serialize_field("d", u)?,
                 Some(RadianceEvent::Ready(r)) => state.serialize_field("d", r)?,
                 Some(RadianceEvent::ReadySupplemental(r)) => state.serialize_field("d", r)?,
                 Some(RadianceEvent::SessionsReplace(v)) => state.serialize_field("d", v)?,
+                Some(RadianceEvent::UserPresenceUpdate(u)) => state.serialize_field("d", u)?,
                 None => {}
                 _ => unreachable!(),
             }
@@ -170,6 +190,7 @@ impl IntoPayload for Event {
     fn into_payload(self, s: &mut u32) -> EventPayload {
         let op = match &self {
             Event::GatewayHello(_) => OpCode::Hello,
+            Event::GatewayHeartbeat(_) => OpCode::Heartbeat,
             Event::GatewayHeartbeatAck => OpCode::HeartbeatAck,
             Event::GatewayInvalidateSession(_) => OpCode::InvalidSession,
             _ => OpCode::Dispatch,
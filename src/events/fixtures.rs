@@ -0,0 +1,66 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::encoding;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Debug;
+
+// Round-trips a captured payload through JSON and ETF. Re-serializing has to
+// reproduce the fixture exactly, so fields the model doesn't know about, and
+// fields the fixture doesn't have, fail the test instead of being dropped or
+// defaulted
+pub fn round_trip<T>(fixture: &str) -> T
+where
+    T: DeserializeOwned + Serialize + PartialEq + Debug,
+{
+    let json = serde_json::from_str::<Value>(fixture).unwrap();
+    let options = encoding::etf_options();
+
+    let from_json = serde_json::from_value::<T>(json.clone())
+        .unwrap_or_else(|e| panic!("JSON doesn't deserialize: {e}"));
+    compare(&json, &serde_json::to_value(&from_json).unwrap(), "d");
+
+    // Clients send binaries for keys and strings for snowflakes, like in JSON
+    let etf = serde_etf::to_vec(&serde_etf::Value::from(json.clone())).unwrap();
+    let from_etf = serde_etf::from_slice_with::<T>(&etf, &options)
+        .unwrap_or_else(|e| panic!("ETF doesn't deserialize: {e}"));
+    assert_eq!(from_etf, from_json);
+    let etf = serde_etf::to_vec(&from_etf).unwrap();
+    let value = serde_etf::from_slice::<serde_etf::Value>(&etf).unwrap();
+    compare(&json, &Value::from(value), "d");
+
+    // The gateway sends atoms for keys and integers for snowflakes
+    let etf = serde_etf::to_vec_with(&from_json, &options).unwrap();
+    let from_etf = serde_etf::from_slice_with::<T>(&etf, &options)
+        .unwrap_or_else(|e| panic!("Gateway ETF doesn't deserialize: {e}"));
+    assert_eq!(from_etf, from_json);
+
+    from_json
+}
+
+fn compare(expected: &Value, actual: &Value, path: &str) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let Some(actual) = actual.get(key) else {
+                    panic!("Unknown field {path}.{key}");
+                };
+                compare(value, actual, &format!("{path}.{key}"));
+            }
+            if let Some(key) = actual.keys().find(|k| !expected.contains_key(*k)) {
+                panic!("Missing field {path}.{key}");
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                compare(expected, actual, &format!("{path}[{i}]"));
+            }
+        }
+        _ => assert_eq!(expected, actual, "Mismatch at {path}"),
+    }
+}
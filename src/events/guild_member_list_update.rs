@@ -0,0 +1,48 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::{ListGroup, ListOp};
+use crate::events::{EventPayload, IntoPayload, RadianceEvent};
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::OpCode;
+use twilight_model::id::marker::GuildMarker;
+use twilight_model::id::Id;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GuildMemberListUpdate {
+    pub guild_id: Id<GuildMarker>,
+    pub id: String,
+    pub member_count: u32,
+    pub online_count: u32,
+    pub groups: Vec<ListGroup>,
+    pub ops: Vec<ListOp>,
+}
+
+impl IntoPayload for GuildMemberListUpdate {
+    fn into_payload(self, s: &mut u32) -> EventPayload {
+        *s += 1;
+
+        EventPayload {
+            op: OpCode::Dispatch,
+            d: Some(RadianceEvent::GuildMemberListUpdate(self)),
+            s: Some(*s),
+            t: Some("GUILD_MEMBER_LIST_UPDATE".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GuildMemberListUpdate;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<GuildMemberListUpdate>(include_str!(
+            "../fixtures/guild_member_list_update.json"
+        ));
+    }
+}
@@ -16,3 +16,14 @@ pub struct Identify {
     pub compress: bool,
     pub client_state: ClientState,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Identify;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<Identify>(include_str!("../fixtures/identify.json"));
+    }
+}
@@ -0,0 +1,38 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::ListRange;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, UserMarker};
+use twilight_model::id::Id;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LazyRequest {
+    pub guild_id: Id<GuildMarker>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typing: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activities: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<Id<UserMarker>>>,
+    // Member list ranges the client wants, per channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<HashMap<Id<ChannelMarker>, Vec<ListRange>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LazyRequest;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<LazyRequest>(include_str!("../fixtures/lazy_request.json"));
+    }
+}
@@ -12,6 +12,7 @@ pub mod consent;
 pub mod friend;
 pub mod guild;
 pub mod guild_supplemental;
+pub mod member_list;
 pub mod merged_presences;
 pub mod notification_settings;
 pub mod personalization;
@@ -33,6 +34,7 @@ pub use consent::*;
 pub use friend::*;
 pub use guild::*;
 pub use guild_supplemental::*;
+pub use member_list::*;
 pub use merged_presences::*;
 pub use notification_settings::*;
 pub use personalization::*;
@@ -10,12 +10,12 @@ use twilight_model::id::Id;
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Friend {
-    pub avatar: String,
+    pub avatar: Option<String>,
     pub avatar_decoration_data: Option<()>,
     pub bot: bool,
     pub discriminator: String,
     pub display_name: String,
-    pub global_name: String,
+    pub global_name: Option<String>,
     pub id: Id<UserMarker>,
     pub public_flags: u32,
     pub username: String,
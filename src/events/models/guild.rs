@@ -32,5 +32,5 @@ pub struct Guild {
     pub stage_instances: Vec<StageInstance>,
     pub stickers: Vec<Sticker>,
     pub threads: Vec<Channel>,
-    pub version: u32, // something like 1706402981462
+    pub version: u64, // something like 1706402981462
 }
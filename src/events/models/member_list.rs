@@ -0,0 +1,99 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::Status;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use twilight_model::gateway::presence::Activity;
+use twilight_model::guild::Member;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+/// A section of the member list. The ID is a hoisted role, `online` or
+/// `offline`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ListGroup {
+    pub id: String,
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListItem {
+    Group(ListGroup),
+    Member(Box<ListMember>),
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ListMember {
+    #[serde(flatten)]
+    pub member: Member,
+    pub presence: MemberPresence,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MemberPresence {
+    pub user: PresenceUser,
+    pub status: Status,
+    pub client_status: ClientStatus,
+    pub activities: Vec<Activity>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PresenceUser {
+    pub id: Id<UserMarker>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClientStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mobile: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<Status>,
+}
+
+/// An inclusive range of list items. It's a list on the wire, which arrays
+/// wouldn't be in ETF.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ListRange(pub u32, pub u32);
+
+impl Serialize for ListRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.0, self.1].as_slice().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ListRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Vec::<u32>::deserialize(deserializer)?[..] {
+            [start, end] => Ok(ListRange(start, end)),
+            ref range => Err(D::Error::invalid_length(range.len(), &"a start and end")),
+        }
+    }
+}
+
+/// Changes to a member list, applied by the client in order.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "UPPERCASE")]
+pub enum ListOp {
+    Sync {
+        range: ListRange,
+        items: Vec<ListItem>,
+    },
+    Insert {
+        index: u32,
+        item: ListItem,
+    },
+    Update {
+        index: u32,
+        item: ListItem,
+    },
+    Delete {
+        index: u32,
+    },
+}
@@ -0,0 +1,37 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::{ClientStatus, MemberPresence, Status};
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::presence::Activity;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MergedPresences {
+    pub friends: Vec<MergedPresence>,
+    // In the same order as READY's guilds
+    pub guilds: Vec<Vec<MergedPresence>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MergedPresence {
+    pub user_id: Id<UserMarker>,
+    pub status: Status,
+    pub client_status: ClientStatus,
+    pub activities: Vec<Activity>,
+}
+
+impl From<MemberPresence> for MergedPresence {
+    fn from(presence: MemberPresence) -> Self {
+        MergedPresence {
+            user_id: presence.user.id,
+            status: presence.status,
+            client_status: presence.client_status,
+            activities: presence.activities,
+        }
+    }
+}
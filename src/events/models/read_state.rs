@@ -9,7 +9,7 @@ use twilight_model::id::marker::MessageMarker;
 use twilight_model::id::Id;
 use twilight_model::util::Timestamp;
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ReadState {
     pub entries: Vec<ReadStateEntry>,
     pub partial: bool,
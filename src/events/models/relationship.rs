@@ -15,6 +15,10 @@ pub struct Relationship {
     pub nickname: Option<String>,
     pub since: Timestamp,
     #[serde(rename = "type")]
-    pub kind: u32, // 3 for friends
+    pub kind: u32, // 1 for friends
     pub user_id: Id<UserMarker>,
 }
+
+impl Relationship {
+    pub const FRIEND: u32 = 1;
+}
@@ -12,3 +12,19 @@ pub enum ReleaseChannel {
     Stable,
     // probably canary and ptb, but im unsure
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ReleaseChannel;
+    use eetf::{Binary, Term};
+
+    #[test]
+    fn etf_round_trip() {
+        let term = serde_etf::to_term(&ReleaseChannel::Stable).unwrap();
+        assert_eq!(term, Term::Binary(Binary::from("stable".as_bytes())));
+        assert_eq!(
+            serde_etf::from_term::<ReleaseChannel>(term).unwrap(),
+            ReleaseChannel::Stable
+        );
+    }
+}
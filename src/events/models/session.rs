@@ -16,7 +16,7 @@ use twilight_model::id::Id;
 pub struct Session {
     pub activities: Vec<Activity>,
     pub client_info: ClientInfo,
-    pub sesion_id: Id<GenericMarker>,
+    pub session_id: Id<GenericMarker>,
     pub status: Status,
 }
 
@@ -32,3 +32,14 @@ impl IntoPayload for Vec<Session> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<Vec<Session>>(include_str!("../../fixtures/sessions_replace.json"));
+    }
+}
@@ -0,0 +1,71 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::presence;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Status {
+    #[serde(rename = "dnd")]
+    DoNotDisturb,
+    #[serde(rename = "idle")]
+    Idle,
+    #[serde(rename = "invisible")]
+    Invisible,
+    #[serde(rename = "offline")]
+    Offline,
+    #[serde(rename = "online")]
+    Online,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+impl From<Status> for presence::Status {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::DoNotDisturb => presence::Status::DoNotDisturb,
+            Status::Idle => presence::Status::Idle,
+            Status::Invisible => presence::Status::Invisible,
+            Status::Offline | Status::Unknown => presence::Status::Offline,
+            Status::Online => presence::Status::Online,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Status;
+    use eetf::{Binary, Term};
+
+    const VARIANTS: [(Status, &str); 6] = [
+        (Status::DoNotDisturb, "dnd"),
+        (Status::Idle, "idle"),
+        (Status::Invisible, "invisible"),
+        (Status::Offline, "offline"),
+        (Status::Online, "online"),
+        (Status::Unknown, "unknown"),
+    ];
+
+    #[test]
+    fn etf_round_trip() {
+        for (status, name) in VARIANTS {
+            let term = serde_etf::to_term(&status).unwrap();
+            assert_eq!(term, Term::Binary(Binary::from(name.as_bytes())));
+            assert_eq!(serde_etf::from_term::<Status>(term).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn etf_matches_json() {
+        for (status, _) in VARIANTS {
+            let json = serde_json::to_value(status).unwrap();
+            let Term::Binary(etf) = serde_etf::to_term(&status).unwrap() else {
+                panic!("{status:?} wasn't encoded as a binary");
+            };
+            assert_eq!(json.as_str().unwrap().as_bytes(), etf.bytes);
+        }
+    }
+}
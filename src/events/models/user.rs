@@ -18,7 +18,7 @@ pub struct User {
     pub banner_color: Option<u32>,
     pub bio: String,
     pub desktop: bool,
-    pub discriminator: u16,
+    pub discriminator: String,
     pub email: String,
     pub flags: i64,
     pub global_name: Option<String>,
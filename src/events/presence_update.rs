@@ -15,3 +15,14 @@ pub struct PresenceUpdate {
     pub activities: Vec<Activity>,
     pub afk: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PresenceUpdate;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<PresenceUpdate>(include_str!("../fixtures/presence_update.json"));
+    }
+}
@@ -57,9 +57,20 @@ impl IntoPayload for Ready {
 
         EventPayload {
             op: OpCode::Dispatch,
-            d: Some(RadianceEvent::Ready(self)),
+            d: Some(RadianceEvent::Ready(Box::new(self))),
             s: Some(*s),
             t: Some("READY".to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Ready;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<Ready>(include_str!("../fixtures/ready.json"));
+    }
+}
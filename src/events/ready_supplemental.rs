@@ -32,3 +32,14 @@ impl IntoPayload for ReadySupplemental {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ReadySupplemental;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<ReadySupplemental>(include_str!("../fixtures/ready_supplemental.json"));
+    }
+}
@@ -14,3 +14,14 @@ pub struct Resume {
     pub session_id: Id<GenericMarker>,
     pub seq: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Resume;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<Resume>(include_str!("../fixtures/resume.json"));
+    }
+}
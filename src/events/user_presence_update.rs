@@ -0,0 +1,46 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::MemberPresence;
+use crate::events::{EventPayload, IntoPayload, RadianceEvent};
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::OpCode;
+use twilight_model::id::marker::GuildMarker;
+use twilight_model::id::Id;
+
+/// A user's presence as it's dispatched to others. Friends get it without a
+/// guild.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UserPresenceUpdate {
+    #[serde(flatten)]
+    pub presence: MemberPresence,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guild_id: Option<Id<GuildMarker>>,
+}
+
+impl IntoPayload for UserPresenceUpdate {
+    fn into_payload(self, s: &mut u32) -> EventPayload {
+        *s += 1;
+
+        EventPayload {
+            op: OpCode::Dispatch,
+            d: Some(RadianceEvent::UserPresenceUpdate(self)),
+            s: Some(*s),
+            t: Some("PRESENCE_UPDATE".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UserPresenceUpdate;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<UserPresenceUpdate>(include_str!("../fixtures/user_presence_update.json"));
+    }
+}
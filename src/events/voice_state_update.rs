@@ -17,3 +17,14 @@ pub struct VoiceStateUpdate {
     pub self_video: bool,
     pub flags: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::VoiceStateUpdate;
+    use crate::events::fixtures::round_trip;
+
+    #[test]
+    fn golden() {
+        round_trip::<VoiceStateUpdate>(include_str!("../fixtures/voice_state_update.json"));
+    }
+}
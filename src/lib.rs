@@ -4,12 +4,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-#[derive(Debug)]
-pub enum GatewayError {
-    ConnectionClosed,
-    InvalidEncoding,
-    IncompleteData,
-    EncodeError,
-    CompressionError,
-    UnexpectedPayload,
-}
+// Everything the binaries share
+pub mod bus;
+pub mod compression;
+pub mod encoding;
+pub mod error;
+pub mod events;
+pub mod store;
+pub mod token;
@@ -0,0 +1,504 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::events::models::{
+    Authentication, Consent, Friend, Guild, GuildSupplemental, MemberPresence, MergedPresence,
+    MergedPresences, NotificationSettings, Personalization, PrivateChannel, ReadState,
+    Relationship, Session, Status, User, UserGuildSettings,
+};
+use crate::events::{Ready, ReadySupplemental};
+use crate::token::TokenKey;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use twilight_model::guild::Member;
+use twilight_model::id::marker::{ChannelMarker, GenericMarker, GuildMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::voice::VoiceState;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Account {
+    pub user: User,
+    pub country_code: String,
+    #[serde(default)]
+    pub current_location: Vec<String>,
+    #[serde(default)]
+    pub guilds: Vec<Id<GuildMarker>>,
+    #[serde(default)]
+    pub private_channels: Vec<PrivateChannel>,
+    #[serde(default)]
+    pub read_state: ReadState,
+    #[serde(default)]
+    pub relationships: Vec<Relationship>,
+    #[serde(default)]
+    pub user_guild_settings: UserGuildSettings,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StoredGuild {
+    pub guild: Guild,
+    #[serde(default)]
+    pub members: Vec<Member>,
+}
+
+// The secret in store.example.json
+const PLACEHOLDER_SECRET: &str = "change-me";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Data {
+    // Signs the tokens accounts log in with
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    rtc_regions: Vec<String>,
+    #[serde(default)]
+    accounts: Vec<Account>,
+    #[serde(default)]
+    guilds: Vec<StoredGuild>,
+}
+
+/// Accounts and the guilds they're in, loaded from a JSON file.
+#[derive(Clone)]
+pub struct AccountStore {
+    data: Arc<Data>,
+    key: TokenKey,
+}
+
+impl AccountStore {
+    /// Reads `RADIANCE_STORE`, falling back to `store.json`.
+    pub fn path() -> PathBuf {
+        std::env::var_os("RADIANCE_STORE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("store.json"))
+    }
+
+    /// Loads the store at `path`. A missing file is an empty store, so nobody
+    /// can log in.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(Self::from_json(&json)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::new(Data::default())),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::from_str(json)?))
+    }
+
+    fn new(data: Data) -> Self {
+        // The example's secret is public, so it's as good as none
+        let secret = match data.secret.as_str() {
+            PLACEHOLDER_SECRET => "",
+            secret => secret,
+        };
+
+        AccountStore {
+            key: TokenKey::new(secret.as_bytes()),
+            data: Arc::new(data),
+        }
+    }
+
+    /// A token the account with `user_id` can log in with.
+    pub fn token(&self, user_id: Id<UserMarker>) -> Option<String> {
+        self.key.sign(user_id, SystemTime::now())
+    }
+
+    pub fn account(&self, user_id: Id<UserMarker>) -> Option<&Account> {
+        self.data.accounts.iter().find(|a| a.user.id == user_id)
+    }
+
+    /// The guild `guild_id`, as long as `user_id` is one of its members.
+    pub fn guild(
+        &self,
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+    ) -> Option<&StoredGuild> {
+        let guild = self.data.guilds.iter().find(|g| g.guild.id == guild_id)?;
+
+        guild
+            .members
+            .iter()
+            .any(|m| m.user.id == user_id)
+            .then_some(guild)
+    }
+
+    /// The guild with the channel `channel_id`, as long as `user_id` is one of
+    /// its members.
+    pub fn channel_guild(
+        &self,
+        user_id: Id<UserMarker>,
+        channel_id: Id<ChannelMarker>,
+    ) -> Option<&StoredGuild> {
+        let guild = self
+            .data
+            .guilds
+            .iter()
+            .find(|g| g.guild.channels.iter().any(|c| c.id == channel_id))?;
+
+        self.guild(user_id, guild.guild.id)
+    }
+
+    /// The account `token` was signed for.
+    pub fn authenticate(&self, token: &str) -> Option<&Account> {
+        let claims = self.key.verify(token, SystemTime::now())?;
+
+        self.account(claims.user_id)
+    }
+
+    /// Everything the account `token` was signed for sees when it logs in.
+    pub fn login(&self, token: &str) -> Option<AccountView> {
+        let account = self.authenticate(token)?;
+        let user_id = account.user.id;
+
+        let guilds = account
+            .guilds
+            .iter()
+            .filter_map(|id| self.data.guilds.iter().find(|g| g.guild.id == *id))
+            .collect::<Vec<_>>();
+
+        // Everyone the account knows directly
+        let known = account
+            .relationships
+            .iter()
+            .map(|r| r.user_id)
+            .chain(
+                account
+                    .private_channels
+                    .iter()
+                    .flat_map(|c| c.recipient_ids.iter().copied()),
+            )
+            .collect::<HashSet<_>>();
+        let friends = account
+            .relationships
+            .iter()
+            .map(|r| r.user_id)
+            .collect::<HashSet<_>>();
+
+        let members_of = |guild: &StoredGuild, users: &dyn Fn(Id<UserMarker>) -> bool| {
+            guild
+                .members
+                .iter()
+                .filter(|m| users(m.user.id))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let users = self
+            .data
+            .accounts
+            .iter()
+            .filter(|a| known.contains(&a.user.id))
+            .map(|a| friend(&a.user))
+            .collect();
+
+        Some(AccountView {
+            account: account.clone(),
+            guilds: guilds
+                .iter()
+                .map(|stored| {
+                    let mut guild = stored.guild.clone();
+                    if let Some(member) = stored.members.iter().find(|m| m.user.id == user_id) {
+                        guild.joined_at = member.joined_at;
+                    }
+                    guild
+                })
+                .collect(),
+            members: guilds
+                .iter()
+                .map(|g| members_of(g, &|id| id == user_id))
+                .collect(),
+            friend_members: guilds
+                .iter()
+                .map(|g| members_of(g, &|id| friends.contains(&id)))
+                .collect(),
+            users,
+            rtc_regions: self.data.rtc_regions.clone(),
+        })
+    }
+}
+
+fn friend(user: &User) -> Friend {
+    Friend {
+        avatar: user.avatar.map(|avatar| avatar.to_string()),
+        avatar_decoration_data: None,
+        bot: false,
+        discriminator: user.discriminator.clone(),
+        display_name: user
+            .global_name
+            .clone()
+            .unwrap_or_else(|| user.username.clone()),
+        global_name: user.global_name.clone(),
+        id: user.id,
+        public_flags: 0,
+        username: user.username.clone(),
+    }
+}
+
+/// An account, together with what it can see of the rest of the store.
+pub struct AccountView {
+    pub account: Account,
+    pub guilds: Vec<Guild>,
+    // The account's own member per guild, in the same order as the guilds
+    pub members: Vec<Vec<Member>>,
+    // Members that are friends of the account, per guild
+    pub friend_members: Vec<Vec<Member>>,
+    // Accounts that are friends or share a private channel with the account
+    pub users: Vec<Friend>,
+    pub rtc_regions: Vec<String>,
+}
+
+impl AccountView {
+    pub fn ready(&self, session_id: Id<GenericMarker>, sessions: Vec<Session>) -> Ready {
+        let account = &self.account;
+
+        Ready {
+            analytics_token: analytics_token(account.user.id),
+            api_code_version: 1,
+            auth: Authentication {
+                authenticator_types: vec![],
+            },
+            auth_session_id_hash: STANDARD
+                .encode(digest(&SHA256, session_id.to_string().as_bytes())),
+            connected_accounts: vec![],
+            consents: Consent {
+                personalization: Personalization { consented: false },
+            },
+            country_code: account.country_code.clone(),
+            current_location: account.current_location.clone(),
+            experiments: vec![],
+            friend_suggestion_count: 0,
+            geo_ordered_rtc_regions: self.rtc_regions.clone(),
+            guild_experiments: vec![],
+            guild_join_requests: vec![],
+            guilds: self.guilds.clone(),
+            merged_members: self.members.clone(),
+            notification_settings: NotificationSettings { flags: 0 },
+            private_channels: account.private_channels.clone(),
+            read_state: account.read_state.clone(),
+            relationships: account.relationships.clone(),
+            required_action: None,
+            resume_gateway_url: "wss://gateway.discord.gg".to_string(),
+            session_id,
+            session_type: "normal".to_string(),
+            sessions,
+            tutorial: None,
+            user: account.user.clone(),
+            user_guild_settings: account.user_guild_settings.clone(),
+            user_settings_proto: "".to_string(),
+            users: self.users.clone(),
+            v: 9,
+        }
+    }
+
+    /// `voice_states` are who's in voice in each guild right now, and
+    /// `presence` is what each user is up to.
+    pub fn ready_supplemental(
+        &self,
+        voice_states: impl Fn(Id<GuildMarker>) -> Vec<VoiceState>,
+        presence: impl Fn(Id<UserMarker>) -> MemberPresence,
+    ) -> ReadySupplemental {
+        ReadySupplemental {
+            disclose: vec!["pomelo".to_string()],
+            game_invites: vec![],
+            guilds: self
+                .guilds
+                .iter()
+                .map(|guild| GuildSupplemental {
+                    embed_activities: vec![],
+                    id: guild.id,
+                    // The guild and member are implied
+                    voice_states: voice_states(guild.id)
+                        .into_iter()
+                        .map(|state| VoiceState {
+                            guild_id: None,
+                            member: None,
+                            ..state
+                        })
+                        .collect(),
+                })
+                .collect(),
+            lazy_private_channels: vec![],
+            merged_members: self.friend_members.clone(),
+            merged_presences: MergedPresences {
+                friends: online(
+                    self.account
+                        .relationships
+                        .iter()
+                        .filter(|r| r.kind == Relationship::FRIEND)
+                        .map(|r| r.user_id),
+                    &presence,
+                ),
+                guilds: self
+                    .friend_members
+                    .iter()
+                    .map(|members| online(members.iter().map(|m| m.user.id), &presence))
+                    .collect(),
+            },
+        }
+    }
+}
+
+// The presences of `users` that aren't offline
+fn online(
+    users: impl Iterator<Item = Id<UserMarker>>,
+    presence: impl Fn(Id<UserMarker>) -> MemberPresence,
+) -> Vec<MergedPresence> {
+    users
+        .map(presence)
+        .filter(|presence| presence.status != Status::Offline)
+        .map(MergedPresence::from)
+        .collect()
+}
+
+// Looks like Discord's, which starts with the user's ID
+fn analytics_token(user_id: Id<UserMarker>) -> String {
+    let mut random = [0; 20];
+    // The token is only used for analytics, so a failure isn't worth much
+    let _ = SystemRandom::new().fill(&mut random);
+
+    format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(user_id.to_string()),
+        URL_SAFE_NO_PAD.encode(random)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::fixtures::round_trip;
+    use crate::events::models::{ClientStatus, PresenceUser};
+
+    const PERSON: Id<UserMarker> = Id::new(967478107550388235);
+    const FRIEND: Id<UserMarker> = Id::new(1209484625727361044);
+
+    fn store() -> AccountStore {
+        let json = include_str!("../store.example.json");
+        AccountStore::from_json(&json.replace(PLACEHOLDER_SECRET, "secret")).unwrap()
+    }
+
+    fn login(store: &AccountStore, user_id: Id<UserMarker>) -> AccountView {
+        store.login(&store.token(user_id).unwrap()).unwrap()
+    }
+
+    fn presence(user: Id<UserMarker>, status: Status) -> MemberPresence {
+        MemberPresence {
+            user: PresenceUser { id: user },
+            status,
+            client_status: ClientStatus::default(),
+            activities: vec![],
+        }
+    }
+
+    fn online(user: Id<UserMarker>) -> MemberPresence {
+        presence(user, Status::Online)
+    }
+
+    fn offline(user: Id<UserMarker>) -> MemberPresence {
+        presence(user, Status::Offline)
+    }
+
+    #[test]
+    fn unknown_tokens_cant_log_in() {
+        let store = store();
+        let token = store.token(PERSON).unwrap();
+
+        assert!(store.login("nope").is_none());
+        // Signed correctly, but there's no such account
+        assert!(store.login(&store.token(Id::new(1)).unwrap()).is_none());
+        // Without a secret, nothing can be verified
+        let empty = AccountStore::load(Path::new("does-not-exist.json")).unwrap();
+        assert!(empty.token(PERSON).is_none());
+        assert!(empty.login(&token).is_none());
+        assert!(empty.authenticate(&token).is_none());
+        // Neither with the example's
+        let example = AccountStore::from_json(include_str!("../store.example.json")).unwrap();
+        assert!(example.token(PERSON).is_none());
+    }
+
+    #[test]
+    fn finds_guilds_by_channel() {
+        let store = store();
+        let channel = Id::new(1201246479711178802);
+
+        let guild = store.channel_guild(PERSON, channel).unwrap();
+        assert_eq!(guild.guild.id, Id::new(1201246479224631326));
+        assert!(store.channel_guild(Id::new(1), channel).is_none());
+        assert!(store.channel_guild(PERSON, Id::new(1)).is_none());
+    }
+
+    #[test]
+    fn accounts_see_their_own_data() {
+        let store = store();
+        let person = login(&store, PERSON);
+        let friend = login(&store, FRIEND);
+
+        let ready = person.ready(Id::new(1), vec![]);
+        assert_eq!(ready.user.username, "person");
+        assert_eq!(ready.users.len(), 1);
+        assert_eq!(ready.users[0].username, "friend");
+        assert_eq!(ready.guilds.len(), 1);
+        assert_eq!(ready.merged_members[0][0].user.id, ready.user.id);
+
+        let ready = friend.ready(Id::new(2), vec![]);
+        assert_eq!(ready.user.username, "friend");
+        assert_eq!(ready.users[0].username, "person");
+        // Each account joined the guild at a different time
+        assert_ne!(ready.guilds[0].joined_at, person.guilds[0].joined_at);
+
+        let guild = ready.guilds[0].id;
+        assert_eq!(store.guild(PERSON, guild).unwrap().members.len(), 2);
+        assert!(store.guild(Id::new(1), guild).is_none());
+
+        let supplemental = person.ready_supplemental(|_| vec![], offline);
+        assert_eq!(supplemental.guilds[0].id, ready.guilds[0].id);
+        assert_eq!(supplemental.merged_members[0][0].user.name, "friend");
+    }
+
+    #[test]
+    fn merges_presences_of_online_friends() {
+        let store = store();
+        // The person connected first and is still online
+        let presence = |user| {
+            presence(
+                user,
+                if user == PERSON {
+                    Status::Online
+                } else {
+                    Status::Offline
+                },
+            )
+        };
+
+        let supplemental = login(&store, FRIEND).ready_supplemental(|_| vec![], presence);
+        let person = [MergedPresence::from(online(PERSON))];
+        assert_eq!(supplemental.merged_presences.friends, person);
+        assert_eq!(supplemental.merged_presences.guilds, [person]);
+
+        let supplemental = login(&store, PERSON).ready_supplemental(|_| vec![], presence);
+        assert!(supplemental.merged_presences.friends.is_empty());
+        assert_eq!(supplemental.merged_presences.guilds, [[]]);
+    }
+
+    #[test]
+    fn ready_round_trips() {
+        let view = login(&store(), PERSON);
+
+        let ready = serde_json::to_string(&view.ready(Id::new(1), vec![])).unwrap();
+        round_trip::<Ready>(&ready);
+        let supplemental =
+            serde_json::to_string(&view.ready_supplemental(|_| vec![], online)).unwrap();
+        round_trip::<ReadySupplemental>(&supplemental);
+    }
+}
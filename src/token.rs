@@ -0,0 +1,118 @@
+// radiance (c) Nikolas Wipper 2024
+
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+/// Signs and verifies tokens. Like Discord's, a token is the user's ID, the
+/// time it was issued and a signature over both, each base64 encoded and
+/// separated by dots.
+#[derive(Clone)]
+pub struct TokenKey {
+    key: Option<hmac::Key>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Claims {
+    pub user_id: Id<UserMarker>,
+    pub issued_at: SystemTime,
+}
+
+impl TokenKey {
+    /// An empty secret can't verify anything, since anyone could sign with it.
+    pub fn new(secret: &[u8]) -> Self {
+        TokenKey {
+            key: (!secret.is_empty()).then(|| hmac::Key::new(hmac::HMAC_SHA256, secret)),
+        }
+    }
+
+    pub fn sign(&self, user_id: Id<UserMarker>, issued_at: SystemTime) -> Option<String> {
+        let seconds = issued_at.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let payload = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(user_id.to_string()),
+            URL_SAFE_NO_PAD.encode(seconds.to_be_bytes())
+        );
+        let signature = hmac::sign(self.key.as_ref()?, payload.as_bytes());
+
+        Some(format!(
+            "{payload}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        ))
+    }
+
+    /// Returns what the token says, if it was signed with this key and wasn't
+    /// issued after `now`.
+    pub fn verify(&self, token: &str, now: SystemTime) -> Option<Claims> {
+        let key = self.key.as_ref()?;
+
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        hmac::verify(key, payload.as_bytes(), &signature).ok()?;
+
+        let (user_id, issued_at) = payload.split_once('.')?;
+        let user_id = String::from_utf8(URL_SAFE_NO_PAD.decode(user_id).ok()?).ok()?;
+        let issued_at = URL_SAFE_NO_PAD.decode(issued_at).ok()?.try_into().ok()?;
+        let issued_at = UNIX_EPOCH + Duration::from_secs(u64::from_be_bytes(issued_at));
+
+        if issued_at > now {
+            return None;
+        }
+
+        Some(Claims {
+            user_id: user_id.parse().ok()?,
+            issued_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_signed_tokens() {
+        let key = TokenKey::new(b"secret");
+        let now = UNIX_EPOCH + Duration::from_secs(1700000000);
+
+        let token = key.sign(Id::new(42), now).unwrap();
+        assert_eq!(
+            key.verify(&token, now + Duration::from_secs(1)),
+            Some(Claims {
+                user_id: Id::new(42),
+                issued_at: now,
+            })
+        );
+
+        // Issued in the future
+        assert_eq!(key.verify(&token, now - Duration::from_secs(1)), None);
+        // Signed with a different key
+        assert_eq!(TokenKey::new(b"other").verify(&token, now), None);
+        // Tampered with
+        let (_, rest) = token.split_once('.').unwrap();
+        let forged = format!("{}.{rest}", URL_SAFE_NO_PAD.encode("43"));
+        assert_eq!(key.verify(&forged, now), None);
+
+        for garbage in ["", "a.b", "a.b.c", "..."] {
+            assert_eq!(key.verify(garbage, now), None);
+        }
+    }
+
+    #[test]
+    fn empty_secrets_verify_nothing() {
+        let key = TokenKey::new(b"");
+
+        assert_eq!(key.sign(Id::new(42), SystemTime::now()), None);
+        let token = TokenKey::new(b"secret")
+            .sign(Id::new(42), SystemTime::now())
+            .unwrap();
+        assert_eq!(key.verify(&token, SystemTime::now()), None);
+    }
+}